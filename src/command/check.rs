@@ -0,0 +1,22 @@
+use std::io::Result;
+
+use crate::service::control::{send_request, ControlRequest};
+
+/// 请求运行中的服务立即检查
+///
+/// # Arguments
+///     republish: 是否重新发布全部记录（即使地址没有变化）
+pub async fn check(republish: bool) -> Result<()> {
+    let request = if republish {
+        ControlRequest::Republish
+    } else {
+        ControlRequest::Check
+    };
+    send_request(request).await?;
+    if republish {
+        println!("Requested the service to republish all records.");
+    } else {
+        println!("Requested the service to check now.");
+    }
+    Ok(())
+}
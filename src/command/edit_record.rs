@@ -0,0 +1,161 @@
+use std::io::{BufRead, Error, ErrorKind, Result, Write};
+use std::net::IpAddr;
+
+use crate::{
+    config::history::{HistoryEntry, HistoryEvent, HistoryLog},
+    service::alidns::dns_operation::AliyunDnsOperate,
+    GLOBAL_CONFIG,
+};
+
+/// 将主机记录指向指定的记录值（不存在时新增）
+pub async fn set_record(hostname: &str, record_type: &str, value: &str, yes: bool) -> Result<()> {
+    let record_type = record_type.to_uppercase();
+    check_value(&record_type, value)?;
+
+    let fqdn = GLOBAL_CONFIG.get().fqdn(hostname);
+    let dns_operate = AliyunDnsOperate::new();
+    let records = dns_operate.find_dns_records(hostname, &record_type).await?;
+
+    match records.first() {
+        Some(record) => {
+            if record.value == value {
+                println!("{} {} already points to {}", fqdn, record_type, value);
+                return Ok(());
+            }
+            if records.len() > 1 {
+                println!(
+                    "Found {} {} records for {}, only the first one will be updated",
+                    records.len(),
+                    record_type,
+                    fqdn
+                );
+            }
+            if !yes
+                && !confirm(&format!(
+                    "Update {} {}: {} -> {}?",
+                    fqdn, record_type, record.value, value
+                ))?
+            {
+                println!("Aborted.");
+                return Ok(());
+            }
+
+            let result = dns_operate
+                .update_dns_record_by_id(&record.record_id, value, &record_type, hostname)
+                .await?;
+            let mut entry = HistoryEntry::new(&fqdn, &record_type, HistoryEvent::Set);
+            entry.old_value = Some(record.value.clone());
+            entry.new_value = Some(value.to_string());
+            entry.request_id = Some(result.request_id.clone());
+            entry.record_id = Some(result.record_id.clone());
+            append_history(entry);
+            println!(
+                "Updated {} {} to {} (RecordId: {}, RequestId: {})",
+                fqdn, record_type, value, result.record_id, result.request_id
+            );
+        }
+        None => {
+            if !yes && !confirm(&format!("Add {} {} -> {}?", fqdn, record_type, value))? {
+                println!("Aborted.");
+                return Ok(());
+            }
+
+            let result = dns_operate
+                .add_dns_record(value, &record_type, hostname)
+                .await?;
+            let mut entry = HistoryEntry::new(&fqdn, &record_type, HistoryEvent::Set);
+            entry.new_value = Some(value.to_string());
+            entry.request_id = Some(result.request_id.clone());
+            entry.record_id = Some(result.record_id.clone());
+            append_history(entry);
+            println!(
+                "Added {} {} -> {} (RecordId: {}, RequestId: {})",
+                fqdn, record_type, value, result.record_id, result.request_id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// 删除主机记录对应类型的全部解析记录
+pub async fn delete_record(hostname: &str, record_type: &str, yes: bool) -> Result<()> {
+    let record_type = record_type.to_uppercase();
+    let fqdn = GLOBAL_CONFIG.get().fqdn(hostname);
+    let dns_operate = AliyunDnsOperate::new();
+    let records = dns_operate.find_dns_records(hostname, &record_type).await?;
+
+    if records.is_empty() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("No record with type {} found for {}", record_type, fqdn),
+        ));
+    }
+
+    let values: Vec<&str> = records.iter().map(|r| r.value.as_str()).collect();
+    if !yes
+        && !confirm(&format!(
+            "Delete {} {} record(s) of {}: {}?",
+            records.len(),
+            record_type,
+            fqdn,
+            values.join(", ")
+        ))?
+    {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    for record in records.iter() {
+        let result = dns_operate.delete_dns_record(&record.record_id).await?;
+        let mut entry = HistoryEntry::new(&fqdn, &record_type, HistoryEvent::Delete);
+        entry.old_value = Some(record.value.clone());
+        entry.request_id = Some(result.request_id.clone());
+        entry.record_id = Some(result.record_id.clone());
+        append_history(entry);
+        println!(
+            "Deleted {} {} -> {} (RecordId: {}, RequestId: {})",
+            fqdn, record_type, record.value, result.record_id, result.request_id
+        );
+    }
+
+    Ok(())
+}
+
+/// 检查 A / AAAA 记录的记录值是否为对应类型的IP地址
+fn check_value(record_type: &str, value: &str) -> Result<()> {
+    let valid = match record_type {
+        "A" => matches!(value.parse::<IpAddr>(), Ok(IpAddr::V4(_))),
+        "AAAA" => matches!(value.parse::<IpAddr>(), Ok(IpAddr::V6(_))),
+        _ => !value.is_empty(),
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid value for {} record: {}", record_type, value),
+        ))
+    }
+}
+
+/// 将手动修改记录到变更历史中
+fn append_history(entry: HistoryEntry) {
+    if !GLOBAL_CONFIG.get().history.enable {
+        return;
+    }
+    if let Err(e) = HistoryLog::append_to_file(&HistoryLog::path(GLOBAL_CONFIG.dir()), &entry) {
+        println!("Failed to write history: {}", e);
+    }
+}
+
+/// 在终端中请求用户确认
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
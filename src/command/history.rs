@@ -0,0 +1,331 @@
+use std::io::{Error, ErrorKind, Result};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+use super::table;
+use crate::{
+    config::history::{HistoryEntry, HistoryEvent, HistoryLog},
+    GLOBAL_CONFIG,
+};
+
+/// 表格的列名
+static HEADERS: [&str; 9] = [
+    "Time",
+    "FQDN",
+    "Type",
+    "Event",
+    "Old",
+    "New",
+    "Source",
+    "RequestId",
+    "Result",
+];
+
+/// 查询条件
+pub struct HistoryQuery {
+    /// 主机记录或完整域名
+    pub name: Option<String>,
+    pub record_type: Option<String>,
+    /// 起始时间戳（含）
+    pub since: Option<i64>,
+    /// 结束时间戳（含）
+    pub until: Option<i64>,
+}
+
+impl HistoryQuery {
+    /// 是否为查询的记录，不考虑时间范围
+    fn matches_record(&self, entry: &HistoryEntry) -> bool {
+        let name_matches = self.name.as_ref().is_none_or(|name| {
+            let name = name.trim_end_matches('.').to_lowercase();
+            let fqdn = entry.fqdn.to_lowercase();
+            fqdn == name || fqdn == GLOBAL_CONFIG.get().fqdn(&name).to_lowercase()
+        });
+        name_matches
+            && self
+                .record_type
+                .as_ref()
+                .is_none_or(|record_type| entry.record_type.eq_ignore_ascii_case(record_type))
+    }
+
+    /// 是否在查询的时间范围内
+    fn in_range(&self, entry: &HistoryEntry) -> bool {
+        self.since.is_none_or(|since| entry.time >= since)
+            && self.until.is_none_or(|until| entry.time <= until)
+    }
+}
+
+/// 输出变更历史
+///
+/// # Arguments
+///     query: 查询条件
+///     at: 指定时仅输出该时间点各记录的记录值，不能与时间范围同时使用
+///     json: 以JSON格式输出
+pub fn history(query: &HistoryQuery, at: Option<i64>, json: bool) -> Result<()> {
+    let entries: Vec<HistoryEntry> = HistoryLog::read(&HistoryLog::path(GLOBAL_CONFIG.dir()))?
+        .into_iter()
+        .filter(|entry| query.matches_record(entry))
+        .collect();
+
+    // 时间点的记录值取决于此前的全部修改，不按时间范围过滤
+    let entries = match at {
+        Some(at) => values_at(entries, at),
+        None => entries
+            .into_iter()
+            .filter(|entry| query.in_range(entry))
+            .collect(),
+    };
+
+    if json {
+        let text = serde_json::to_string_pretty(&entries)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        println!("{}", text);
+    } else {
+        print_table(&entries);
+    }
+    Ok(())
+}
+
+/// 各记录在指定时间点的记录值：该时间点之前最后一次成功的修改
+fn values_at(entries: Vec<HistoryEntry>, at: i64) -> Vec<HistoryEntry> {
+    let mut latest: Vec<HistoryEntry> = Vec::new();
+    for entry in entries.into_iter().filter(|entry| {
+        entry.time <= at
+            && entry.success
+            && matches!(
+                entry.event,
+                HistoryEvent::Update | HistoryEvent::Set | HistoryEvent::Delete
+            )
+    }) {
+        match latest
+            .iter_mut()
+            .find(|e| e.fqdn == entry.fqdn && e.record_type == entry.record_type)
+        {
+            Some(existing) if existing.time <= entry.time => *existing = entry,
+            Some(_) => (),
+            None => latest.push(entry),
+        }
+    }
+    latest.sort_by(|a, b| a.fqdn.cmp(&b.fqdn).then(a.record_type.cmp(&b.record_type)));
+    latest
+}
+
+/// 以表格形式输出变更历史
+fn print_table(entries: &[HistoryEntry]) {
+    let optional = |value: &Option<String>| value.clone().unwrap_or_else(|| String::from("-"));
+    let rows: Vec<[String; 9]> = entries
+        .iter()
+        .map(|entry| {
+            [
+                table::format_timestamp_millis(entry.time.saturating_mul(1000)),
+                entry.fqdn.clone(),
+                entry.record_type.clone(),
+                entry.event.to_string(),
+                optional(&entry.old_value),
+                optional(&entry.new_value),
+                optional(&entry.source),
+                optional(&entry.request_id),
+                match &entry.error {
+                    Some(error) => format!("failed: {}", error),
+                    None if entry.success => String::from("ok"),
+                    None => String::from("failed"),
+                },
+            ]
+        })
+        .collect();
+
+    table::print_table(&HEADERS, &rows);
+    println!("{} entries", entries.len());
+}
+
+/// 解析查询时间，支持以下格式（不含时区时按本地时间）：
+///     RFC 3339（如 `2024-05-01T08:00:00+08:00`）、`YYYY-MM-DD HH:MM[:SS]`、`YYYY-MM-DD`，
+///     以及相对于当前的时间（如 `30m`、`12h`、`7d`）
+///
+/// # Arguments
+///     end_of_day: 仅指定日期时是否取当天结束时刻（用于结束时间）
+pub fn parse_time(text: &str, end_of_day: bool) -> Result<i64> {
+    let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid time: {}", text));
+
+    // 相对时间
+    if let Some(unit) = text.chars().last().filter(|c| matches!(c, 'm' | 'h' | 'd')) {
+        if let Ok(amount) = text[..text.len() - 1].parse::<u64>() {
+            let seconds = match unit {
+                'm' => 60,
+                'h' => 60 * 60,
+                _ => 24 * 60 * 60,
+            };
+            return i64::try_from(amount)
+                .ok()
+                .filter(|amount| *amount > 0)
+                .and_then(|amount| amount.checked_mul(seconds))
+                .and_then(|offset| chrono::Utc::now().timestamp().checked_sub(offset))
+                .ok_or_else(invalid);
+        }
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.timestamp());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(text, format) {
+            return local_timestamp(time).ok_or_else(invalid);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        let time = if end_of_day {
+            date.and_hms_opt(23, 59, 59)
+        } else {
+            date.and_hms_opt(0, 0, 0)
+        };
+        return time.and_then(local_timestamp).ok_or_else(invalid);
+    }
+    Err(invalid())
+}
+
+fn local_timestamp(time: NaiveDateTime) -> Option<i64> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|time| time.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        time: i64,
+        fqdn: &str,
+        event: HistoryEvent,
+        value: &str,
+        success: bool,
+    ) -> HistoryEntry {
+        let mut entry = HistoryEntry::new(fqdn, "A", event);
+        entry.time = time;
+        entry.new_value = Some(value.to_string());
+        entry.success = success;
+        entry
+    }
+
+    #[test]
+    fn parse_relative_time() {
+        let now = chrono::Utc::now().timestamp();
+        for (text, offset) in [
+            ("30m", 30 * 60),
+            ("12h", 12 * 60 * 60),
+            ("7d", 7 * 24 * 60 * 60),
+        ] {
+            let time = parse_time(text, false).unwrap();
+            assert!(
+                (now - offset..=now - offset + 5).contains(&time),
+                "{}",
+                text
+            );
+        }
+
+        // 溢出、负数及零均不合法
+        for text in [
+            "999999999999999999d",
+            "18446744073709551615m",
+            "-5d",
+            "0h",
+            "d",
+        ] {
+            assert!(parse_time(text, false).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn parse_absolute_time() {
+        assert_eq!(
+            parse_time("2024-05-01T08:00:00+08:00", false).unwrap(),
+            1_714_521_600
+        );
+        assert_eq!(
+            parse_time("2024-05-01T00:00:00Z", true).unwrap(),
+            1_714_521_600
+        );
+
+        let local = |text: &str| {
+            let time = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap();
+            local_timestamp(time).unwrap()
+        };
+        assert_eq!(
+            parse_time("2024-05-01 08:30", false).unwrap(),
+            local("2024-05-01 08:30:00")
+        );
+        assert_eq!(
+            parse_time("2024-05-01 08:30:15", true).unwrap(),
+            local("2024-05-01 08:30:15")
+        );
+
+        // 仅指定日期时取当天开始或结束时刻
+        assert_eq!(
+            parse_time("2024-05-01", false).unwrap(),
+            local("2024-05-01 00:00:00")
+        );
+        assert_eq!(
+            parse_time("2024-05-01", true).unwrap(),
+            local("2024-05-01 23:59:59")
+        );
+
+        for text in ["2024-13-01", "yesterday", ""] {
+            assert!(parse_time(text, false).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn values_at_time() {
+        let entries = vec![
+            entry(
+                100,
+                "www.example.com",
+                HistoryEvent::Update,
+                "192.0.2.1",
+                true,
+            ),
+            entry(
+                200,
+                "www.example.com",
+                HistoryEvent::Detected,
+                "192.0.2.2",
+                true,
+            ),
+            entry(
+                300,
+                "www.example.com",
+                HistoryEvent::Update,
+                "192.0.2.3",
+                false,
+            ),
+            entry(400, "www.example.com", HistoryEvent::Set, "192.0.2.4", true),
+            entry(
+                150,
+                "api.example.com",
+                HistoryEvent::Update,
+                "192.0.2.9",
+                true,
+            ),
+        ];
+
+        // 只考虑该时间点之前成功的修改
+        let values = values_at(entries.clone(), 350);
+        let values: Vec<(&str, Option<&str>)> = values
+            .iter()
+            .map(|entry| (entry.fqdn.as_str(), entry.new_value.as_deref()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("api.example.com", Some("192.0.2.9")),
+                ("www.example.com", Some("192.0.2.1")),
+            ]
+        );
+
+        assert_eq!(
+            values_at(entries.clone(), 400)[1].new_value.as_deref(),
+            Some("192.0.2.4")
+        );
+        assert!(values_at(entries, 99).is_empty());
+    }
+}
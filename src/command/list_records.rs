@@ -0,0 +1,66 @@
+use std::io::{Error, ErrorKind, Result};
+
+use super::table;
+use crate::{
+    service::alidns::dns_operation::{AliyunDnsOperate, DnsRecord},
+    GLOBAL_CONFIG,
+};
+
+/// 表格的列名
+static HEADERS: [&str; 8] = [
+    "RR", "Type", "Value", "TTL", "Line", "Status", "Locked", "Updated",
+];
+
+/// 列出域名下的解析记录
+pub async fn list_records(json: bool, configured_only: bool) -> Result<()> {
+    let dns_operate = AliyunDnsOperate::new();
+    let mut records = dns_operate.get_all_dns_records().await?;
+
+    // 仅保留配置文件中涉及的主机记录
+    if configured_only {
+        let config = GLOBAL_CONFIG.get();
+        records.retain(|record| {
+            config
+                .records
+                .iter()
+                .any(|monitor_record| monitor_record.hostname == record.rr)
+        });
+    }
+    records.sort_by(|a, b| a.rr.cmp(&b.rr).then(a.record_type.cmp(&b.record_type)));
+
+    if json {
+        let text = serde_json::to_string_pretty(&records)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        println!("{}", text);
+    } else {
+        print_table(&records);
+    }
+
+    Ok(())
+}
+
+/// 以表格形式输出解析记录
+fn print_table(records: &[DnsRecord]) {
+    let rows: Vec<[String; 8]> = records
+        .iter()
+        .map(|record| {
+            [
+                record.rr.clone(),
+                record.record_type.clone(),
+                record.value.clone(),
+                record.ttl.to_string(),
+                record.line.clone(),
+                record.status.clone(),
+                record.locked.to_string(),
+                table::format_timestamp_millis(record.update_timestamp),
+            ]
+        })
+        .collect();
+
+    table::print_table(&HEADERS, &rows);
+    println!(
+        "{} record(s) in {}",
+        records.len(),
+        GLOBAL_CONFIG.get().domain_name
+    );
+}
@@ -0,0 +1,310 @@
+pub mod check;
+pub mod edit_record;
+pub mod history;
+pub mod list_records;
+mod table;
+
+use std::env;
+use std::io::{Error, ErrorKind, Result};
+
+use crate::GLOBAL_CONFIG;
+
+/// 命令行子命令
+pub enum Command {
+    /// 列出域名下的解析记录
+    ListRecords {
+        /// 以JSON格式输出
+        json: bool,
+        /// 仅输出配置文件中`[[record]]`涉及的主机记录
+        configured_only: bool,
+    },
+    /// 手动设置解析记录
+    Set {
+        hostname: String,
+        record_type: String,
+        value: String,
+        /// 跳过确认提示
+        yes: bool,
+    },
+    /// 手动删除解析记录
+    Delete {
+        hostname: String,
+        record_type: String,
+        /// 跳过确认提示
+        yes: bool,
+    },
+    /// 查询变更历史
+    History {
+        query: history::HistoryQuery,
+        /// 仅输出该时间点各记录的记录值
+        at: Option<i64>,
+        /// 以JSON格式输出
+        json: bool,
+    },
+    /// 请求运行中的服务立即检查
+    Check {
+        /// 重新发布全部记录
+        republish: bool,
+    },
+}
+
+/// 从命令行参数中解析子命令
+///
+/// # Return
+///     Result<Option<Command>>: 未指定子命令时返回None，即以服务方式运行
+pub fn parse_command() -> Result<Option<Command>> {
+    parse_args(&env::args().skip(1).collect::<Vec<String>>())
+}
+
+/// 解析命令行参数（不含程序名），全局参数可位于子命令之前或之后
+fn parse_args(args: &[String]) -> Result<Option<Command>> {
+    let mut it = args.iter();
+
+    // 跳过全局参数，找到第一个位置参数作为子命令
+    let mut command_name = None;
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "-c" | "--config" | "-t" | "--test" => {
+                it.next();
+            }
+            _ if arg.starts_with('-') => (),
+            _ => {
+                command_name = Some(arg.as_str());
+                break;
+            }
+        }
+    }
+
+    // 子命令之后的全局参数及其取值
+    let mut command_args: Vec<&String> = Vec::new();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "-c" | "--config" | "-t" | "--test" => {
+                it.next();
+            }
+            _ => command_args.push(arg),
+        }
+    }
+    match command_name {
+        None => Ok(None),
+        Some("list-records") => {
+            let mut json = false;
+            let mut configured_only = false;
+            for arg in command_args {
+                match arg.as_str() {
+                    "--json" => json = true,
+                    "--configured" => configured_only = true,
+                    _ => return Err(unknown_argument("list-records", arg)),
+                }
+            }
+            Ok(Some(Command::ListRecords {
+                json,
+                configured_only,
+            }))
+        }
+        Some("set") => {
+            let (positional, yes) = split_yes_flag("set", command_args)?;
+            match positional.as_slice() {
+                [hostname, record_type, value] => Ok(Some(Command::Set {
+                    hostname: hostname.to_string(),
+                    record_type: record_type.to_string(),
+                    value: value.to_string(),
+                    yes,
+                })),
+                _ => Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Usage: set <hostname> <type> <value> [--yes]",
+                )),
+            }
+        }
+        Some("delete") => {
+            let (positional, yes) = split_yes_flag("delete", command_args)?;
+            match positional.as_slice() {
+                [hostname, record_type] => Ok(Some(Command::Delete {
+                    hostname: hostname.to_string(),
+                    record_type: record_type.to_string(),
+                    yes,
+                })),
+                _ => Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Usage: delete <hostname> <type> [--yes]",
+                )),
+            }
+        }
+        Some("history") => {
+            let mut query = history::HistoryQuery {
+                name: None,
+                record_type: None,
+                since: None,
+                until: None,
+            };
+            let mut at = None;
+            let mut json = false;
+            let mut args = command_args.into_iter();
+            while let Some(arg) = args.next() {
+                let mut value = || {
+                    args.next().ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("Missing value for {}", arg),
+                        )
+                    })
+                };
+                match arg.as_str() {
+                    "--type" => query.record_type = Some(value()?.to_uppercase()),
+                    "--since" => query.since = Some(history::parse_time(value()?, false)?),
+                    "--until" => query.until = Some(history::parse_time(value()?, true)?),
+                    "--at" => at = Some(history::parse_time(value()?, true)?),
+                    "--json" => json = true,
+                    _ if arg.starts_with("--") => return Err(unknown_argument("history", arg)),
+                    _ if query.name.is_none() => query.name = Some(arg.to_string()),
+                    _ => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "Usage: history [<hostname|fqdn>] [--type <type>] [--since <time>] [--until <time>] [--at <time>] [--json]",
+                        ))
+                    }
+                }
+            }
+            if at.is_some() && (query.since.is_some() || query.until.is_some()) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "--at cannot be combined with --since or --until",
+                ));
+            }
+            Ok(Some(Command::History { query, at, json }))
+        }
+        Some("check") => {
+            let mut republish = false;
+            for arg in command_args {
+                match arg.as_str() {
+                    "--republish" => republish = true,
+                    _ => return Err(unknown_argument("check", arg)),
+                }
+            }
+            Ok(Some(Command::Check { republish }))
+        }
+        Some(name) => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unknown command: {}", name),
+        )),
+    }
+}
+
+/// 执行子命令
+pub fn run_command(command: Command) -> Result<()> {
+    // 子命令依赖配置文件中的域名及认证信息
+    if GLOBAL_CONFIG.dir().is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Please specify the config directory with -c | --config PATH.",
+        ));
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(async move {
+        match command {
+            Command::ListRecords {
+                json,
+                configured_only,
+            } => list_records::list_records(json, configured_only).await,
+            Command::Set {
+                hostname,
+                record_type,
+                value,
+                yes,
+            } => edit_record::set_record(&hostname, &record_type, &value, yes).await,
+            Command::Delete {
+                hostname,
+                record_type,
+                yes,
+            } => edit_record::delete_record(&hostname, &record_type, yes).await,
+            Command::History { query, at, json } => history::history(&query, at, json),
+            Command::Check { republish } => check::check(republish).await,
+        }
+    })
+}
+
+/// 分离位置参数与 `-y | --yes` 选项
+fn split_yes_flag<'a>(command: &str, args: Vec<&'a String>) -> Result<(Vec<&'a String>, bool)> {
+    let mut positional = Vec::new();
+    let mut yes = false;
+    for arg in args {
+        match arg.as_str() {
+            "-y" | "--yes" => yes = true,
+            _ if arg.starts_with("--") => return Err(unknown_argument(command, arg)),
+            _ => positional.push(arg),
+        }
+    }
+    Ok((positional, yes))
+}
+
+fn unknown_argument(command: &str, arg: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!("Unknown argument for {}: {}", command, arg),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Command>> {
+        parse_args(
+            &args
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<String>>(),
+        )
+    }
+
+    #[test]
+    fn global_flags_in_any_position() {
+        for args in [
+            &["-c", "/etc/aliyun-ddns", "list-records", "--json"][..],
+            &["list-records", "-c", "/etc/aliyun-ddns", "--json"],
+            &["list-records", "--json", "--config", "/etc/aliyun-ddns"],
+        ] {
+            assert!(
+                matches!(
+                    parse(args),
+                    Ok(Some(Command::ListRecords {
+                        json: true,
+                        configured_only: false
+                    }))
+                ),
+                "{:?}",
+                args
+            );
+        }
+
+        match parse(&["set", "www", "-c", "/etc/aliyun-ddns", "A", "192.0.2.1"]) {
+            Ok(Some(Command::Set {
+                hostname,
+                record_type,
+                value,
+                yes: false,
+            })) => assert_eq!(
+                (hostname.as_str(), record_type.as_str(), value.as_str()),
+                ("www", "A", "192.0.2.1")
+            ),
+            _ => panic!("set not parsed"),
+        }
+        assert!(matches!(parse(&["-c", "/etc/aliyun-ddns"]), Ok(None)));
+        assert!(parse(&["list-records", "--unknown"]).is_err());
+    }
+
+    #[test]
+    fn history_at_excludes_time_range() {
+        assert!(matches!(
+            parse(&["history", "www", "--at", "2024-05-01"]),
+            Ok(Some(Command::History { at: Some(_), .. }))
+        ));
+        assert!(parse(&["history", "--at", "2024-05-01", "--since", "7d"]).is_err());
+        assert!(parse(&["history", "--until", "1h", "--at", "2024-05-01"]).is_err());
+    }
+}
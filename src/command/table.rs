@@ -0,0 +1,36 @@
+use chrono::{Local, TimeZone};
+
+/// 以表格形式输出，各列按最宽的单元格左对齐
+pub fn print_table<const N: usize>(headers: &[&str; N], rows: &[[String; N]]) {
+    // 计算每列的宽度
+    let mut widths = headers.map(|header| header.chars().count());
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| -> String {
+        cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", format_row(headers.to_vec()));
+    for row in rows.iter() {
+        println!("{}", format_row(row.iter().map(|s| s.as_str()).collect()));
+    }
+}
+
+/// 将毫秒时间戳格式化为本地时间
+pub fn format_timestamp_millis(timestamp_millis: i64) -> String {
+    match Local.timestamp_millis_opt(timestamp_millis).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => String::from("-"),
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    path::PathBuf,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use tokio::sync::watch;
+
+use super::{load_config::reload_config_file, Config};
+
+/// 可重新加载的配置
+///
+/// 通过 `get` 取得当前配置的快照；重新加载成功后替换配置，并通知 `subscribe` 得到的接收端
+pub struct ConfigHandle {
+    /// 配置文件所在目录，同时用于存放记录文件；未指定配置文件时为空
+    config_dir: String,
+    config: RwLock<Arc<Config>>,
+    /// 配置的版本号，每次重新加载后加一
+    version: watch::Sender<u64>,
+}
+
+impl ConfigHandle {
+    pub fn new((config_dir, config): (String, Config)) -> ConfigHandle {
+        ConfigHandle {
+            config_dir,
+            config: RwLock::new(Arc::new(config)),
+            version: watch::Sender::new(0),
+        }
+    }
+
+    /// 配置文件所在目录
+    pub fn dir(&self) -> &str {
+        &self.config_dir
+    }
+
+    /// 配置文件的路径
+    pub fn file_path(&self) -> PathBuf {
+        PathBuf::from(&self.config_dir).join("config.toml")
+    }
+
+    /// 当前配置
+    pub fn get(&self) -> Arc<Config> {
+        self.config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// 重新读取并检查配置文件，成功后替换当前配置并通知订阅者，失败时保留当前配置
+    pub fn reload(&self) -> Result<()> {
+        if self.config_dir.is_empty() {
+            return Err(Error::new(ErrorKind::NotFound, "No config file to reload."));
+        }
+        let config = reload_config_file(&self.config_dir)?;
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+        self.version.send_modify(|version| *version += 1);
+        Ok(())
+    }
+
+    /// 订阅配置重新加载的通知
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version.subscribe()
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    fmt::{self, Display},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Result, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use super::History;
+
+/// 两次清理历史之间的最短间隔
+static PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 变更事件的类型
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HistoryEvent {
+    /// 检测到地址变化（已确认）
+    Detected,
+    /// 校正时发现解析记录的实际值与检测到的地址不一致
+    Drift,
+    /// 调用接口更新解析记录
+    Update,
+    /// 通过 `set` 命令手动设置解析记录
+    Set,
+    /// 通过 `delete` 命令手动删除解析记录
+    Delete,
+}
+
+impl Display for HistoryEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryEvent::Detected => write!(f, "detected"),
+            HistoryEvent::Drift => write!(f, "drift"),
+            HistoryEvent::Update => write!(f, "update"),
+            HistoryEvent::Set => write!(f, "set"),
+            HistoryEvent::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+/// 变更历史中的一条记录
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    /// 时间戳（单位：秒）
+    pub time: i64,
+    pub fqdn: String,
+    pub record_type: String,
+    pub event: HistoryEvent,
+    /// 变更前的记录值
+    #[serde(default)]
+    pub old_value: Option<String>,
+    /// 变更后的记录值
+    #[serde(default)]
+    pub new_value: Option<String>,
+    /// 地址的来源，如 `nic:eth0`、`http`
+    #[serde(default)]
+    pub source: Option<String>,
+    /// 阿里云接口返回的请求ID
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// 阿里云解析记录ID
+    #[serde(default)]
+    pub record_id: Option<String>,
+    /// 是否成功
+    pub success: bool,
+    /// 失败原因
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    /// 创建一条成功的记录，其余字段按需填写
+    pub fn new(fqdn: &str, record_type: &str, event: HistoryEvent) -> HistoryEntry {
+        HistoryEntry {
+            time: chrono::Utc::now().timestamp(),
+            fqdn: fqdn.to_string(),
+            record_type: record_type.to_string(),
+            event,
+            old_value: None,
+            new_value: None,
+            source: None,
+            request_id: None,
+            record_id: None,
+            success: true,
+            error: None,
+        }
+    }
+}
+
+/// 变更历史（JSON Lines，单独存储于记录目录中的 `history.jsonl`）
+pub struct HistoryLog {
+    history_file_path: PathBuf,
+    enable: bool,
+    max_age: u64,
+    max_entries: usize,
+    last_prune: Option<Instant>,
+}
+
+impl HistoryLog {
+    pub fn new(record_file_dir: &str, config: &History) -> HistoryLog {
+        HistoryLog {
+            history_file_path: Self::path(record_file_dir),
+            enable: config.enable,
+            max_age: config.max_age,
+            max_entries: config.max_entries,
+            last_prune: None,
+        }
+    }
+
+    /// 变更历史文件的路径
+    pub fn path(record_file_dir: &str) -> PathBuf {
+        Path::new(record_file_dir).join("history.jsonl")
+    }
+
+    /// 追加一条记录，写入失败时仅给出警告
+    pub fn append(&mut self, entry: HistoryEntry) {
+        if !self.enable {
+            return;
+        }
+        if let Err(e) = Self::append_to_file(&self.history_file_path, &entry) {
+            warn!(
+                "Failed to write history file {}: {}",
+                self.history_file_path.display(),
+                e
+            );
+        }
+
+        // 按保留规则定期清理
+        if self
+            .last_prune
+            .is_none_or(|last_prune| last_prune.elapsed() >= PRUNE_INTERVAL)
+        {
+            self.last_prune = Some(Instant::now());
+            if let Err(e) = self.prune() {
+                warn!("Failed to prune history file: {}", e);
+            }
+        }
+    }
+
+    /// 以追加方式写入一条记录，供不持有 `HistoryLog` 的命令使用
+    pub fn append_to_file(path: &Path, entry: &HistoryEntry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let _lock = Self::lock(path)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(line.as_bytes())
+    }
+
+    /// 删除超过保留天数或条数的记录
+    fn prune(&self) -> Result<()> {
+        if self.max_age == 0 && self.max_entries == 0 {
+            return Ok(());
+        }
+        // 持有锁直至替换完成，避免期间其他进程追加的记录丢失
+        let _lock = Self::lock(&self.history_file_path)?;
+        let entries = Self::read(&self.history_file_path)?;
+        let total = entries.len();
+
+        let oldest = i64::try_from(self.max_age)
+            .ok()
+            .and_then(|days| days.checked_mul(24 * 60 * 60))
+            .map_or(i64::MIN, |max_age| {
+                chrono::Utc::now().timestamp().saturating_sub(max_age)
+            });
+        let mut kept: Vec<&HistoryEntry> = entries
+            .iter()
+            .filter(|entry| self.max_age == 0 || entry.time >= oldest)
+            .collect();
+        if self.max_entries > 0 && kept.len() > self.max_entries {
+            kept.drain(..kept.len() - self.max_entries);
+        }
+        if kept.len() == total {
+            return Ok(());
+        }
+
+        // 写入临时文件后替换，避免清理过程中崩溃导致历史丢失
+        let temp_path = self.history_file_path.with_extension("jsonl.tmp");
+        {
+            let mut temp_file = File::create(&temp_path)?;
+            for entry in kept.iter() {
+                let mut line = serde_json::to_string(entry)?;
+                line.push('\n');
+                temp_file.write_all(line.as_bytes())?;
+            }
+            temp_file.sync_all()?;
+        }
+        fs::rename(&temp_path, &self.history_file_path)?;
+        debug!("Pruned {} history entries", total - kept.len());
+        Ok(())
+    }
+
+    /// 锁定变更历史文件（`history.jsonl.lock`），等待其他进程的追加或清理完成
+    ///
+    /// 清理时会替换历史文件，因此锁加在单独的文件上；仅Unix系统支持，锁在返回的文件关闭时释放
+    fn lock(path: &Path) -> Result<File> {
+        let lock_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.with_extension("jsonl.lock"))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+
+            // SAFETY: 描述符在调用期间有效
+            if unsafe { libc::flock(lock_file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(lock_file)
+    }
+
+    /// 读取全部记录，文件不存在时返回空列表，无法解析的行将被跳过
+    pub fn read(path: &Path) -> Result<Vec<HistoryEntry>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => debug!("Skipping invalid history line: {}", e),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    /// 在新的临时目录中创建变更历史，目录随返回的 TempDir 删除
+    fn history_log(max_age: u64, max_entries: usize) -> (TempDir, HistoryLog) {
+        let dir = TempDir::new("history");
+        let history = History {
+            enable: true,
+            max_age,
+            max_entries,
+        };
+        let log = HistoryLog::new(dir.to_str(), &history);
+        (dir, log)
+    }
+
+    fn write_entries(log: &HistoryLog, times: &[i64]) {
+        for time in times {
+            let mut entry = HistoryEntry::new("www.example.com", "A", HistoryEvent::Update);
+            entry.time = *time;
+            HistoryLog::append_to_file(&log.history_file_path, &entry).unwrap();
+        }
+    }
+
+    fn times(log: &HistoryLog) -> Vec<i64> {
+        HistoryLog::read(&log.history_file_path)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.time)
+            .collect()
+    }
+
+    #[test]
+    fn prune_by_age_and_count() {
+        let now = chrono::Utc::now().timestamp();
+        let day = 24 * 60 * 60;
+
+        let (_dir, log) = history_log(7, 0);
+        write_entries(&log, &[now - 30 * day, now - 8 * day, now - day, now]);
+        log.prune().unwrap();
+        assert_eq!(times(&log), vec![now - day, now]);
+
+        let (_dir, log) = history_log(0, 2);
+        write_entries(&log, &[1, 2, 3]);
+        log.prune().unwrap();
+        assert_eq!(times(&log), vec![2, 3]);
+    }
+
+    #[test]
+    fn prune_with_huge_max_age() {
+        for max_age in [u64::MAX, i64::MAX as u64, u64::MAX / (24 * 60 * 60)] {
+            let (_dir, log) = history_log(max_age, 0);
+            write_entries(&log, &[0, 1]);
+            log.prune().unwrap();
+            assert_eq!(times(&log), vec![0, 1]);
+        }
+    }
+}
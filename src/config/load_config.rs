@@ -1,8 +1,13 @@
 use std::env;
 use std::fs::File;
 use std::io::{Error, ErrorKind, Read, Result};
+use std::net::{Ipv4Addr, SocketAddrV4, SocketAddrV6};
 
-use crate::config::Config;
+use crate::config::{
+    select::{AddressRange, AddressScope, AddressSuffix},
+    source::SourceSpec,
+    Config,
+};
 
 /// 解析参数并读取配置文件
 ///
@@ -20,6 +25,7 @@ pub fn load_server_config() -> Result<(String, Config)> {
 
     // Args:    -c | --config PATH     > Path to the configuration file
     //          -h | --help            > Show help message
+    // Commands: see crate::command::parse_command
 
     while arg.is_some() {
         match arg.unwrap().as_str() {
@@ -36,10 +42,8 @@ pub fn load_server_config() -> Result<(String, Config)> {
                     }
                     Some(config_file_path_arg) => {
                         config_path = config_file_path_arg.clone();
-                        config = parse_and_read_config_file(&format!(
-                            "{}/config.toml",
-                            config_file_path_arg
-                        ))?;
+                        config =
+                            parse_and_read_config_file(&config_file_path(config_file_path_arg))?;
                         println!(
                             "ConfigLoad: [Warning] Using config file. CLI args will be ignored."
                         );
@@ -103,6 +107,24 @@ pub fn load_server_config() -> Result<(String, Config)> {
                 println!("\t-t | --test PATH    > Test the configuration file");
                 println!("\t-v | --version      > Show version information");
                 println!("\t-h | --help         > Show help message");
+                println!("Commands (require -c | --config PATH): ");
+                println!("\tlist-records [--json] [--configured]");
+                println!("\t\t> List DNS records of the domain, optionally only the hostnames in [[record]]");
+                println!("\tset <hostname> <type> <value> [--yes]");
+                println!("\t\t> Point a record to the value, adding it if it does not exist");
+                println!("\tdelete <hostname> <type> [--yes]");
+                println!("\t\t> Delete all records of the hostname with the type");
+                println!("\thistory [<hostname|fqdn>] [--type <type>] [--since <time>] [--until <time>] [--at <time>] [--json]");
+                println!(
+                    "\t\t> Show the change history, or the values in effect at a time with --at"
+                );
+                println!(
+                    "\t\t  Time: YYYY-MM-DD[ HH:MM[:SS]], RFC 3339, or relative such as 12h, 7d"
+                );
+                println!("\tcheck [--republish]");
+                println!(
+                    "\t\t> Ask the running service to check now (same as SIGUSR1), or to republish all records (same as SIGUSR2)"
+                );
                 return Err(Error::new(ErrorKind::Other, "Help message displayed."));
             }
             _ => {
@@ -114,6 +136,18 @@ pub fn load_server_config() -> Result<(String, Config)> {
     return Ok((config_path, config));
 }
 
+/// 配置目录中配置文件的路径
+fn config_file_path(config_dir: &str) -> String {
+    format!("{}/config.toml", config_dir)
+}
+
+/// 重新读取并检查配置目录中的配置文件
+pub fn reload_config_file(config_dir: &str) -> Result<Config> {
+    let config = parse_and_read_config_file(&config_file_path(config_dir))?;
+    check_config(&config)?;
+    Ok(config)
+}
+
 /// 解析并读取配置文件
 fn parse_and_read_config_file(config_file_path_arg: &String) -> Result<Config> {
     let mut config_file = File::open(config_file_path_arg)?;
@@ -165,6 +199,108 @@ fn check_config(config: &Config) -> Result<()> {
         if record.nic_name.is_some() && record.nic_name.as_ref().unwrap().is_empty() {
             return Err(Error::new(ErrorKind::InvalidData, "NIC name is empty."));
         }
+        let nic_selectors = [
+            record.nic_name.is_some(),
+            record.nic_mac.is_some(),
+            record.nic_pattern.is_some(),
+            record.nic_default_route,
+        ];
+        if nic_selectors.iter().filter(|selected| **selected).count() > 1 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Only one of nic-name, nic-mac, nic-pattern and nic-default-route can be set.",
+            ));
+        }
+        if let Some(selector) = record.nic_selector() {
+            selector.check()?;
+        }
+        // 检查地址选择规则
+        record.scope.parse::<AddressScope>()?;
+        record.address_policy()?;
+        // 检查前缀委派配置
+        if let Some(suffix) = &record.suffix {
+            if record.record_type != "AAAA" {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "Address suffix is only valid for AAAA records.",
+                ));
+            }
+            suffix.parse::<AddressSuffix>()?;
+        }
+        if record.prefix_length == 0 || record.prefix_length > 128 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Prefix length is invalid.",
+            ));
+        }
+        if record.confirm_count == Some(0) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Confirm count is invalid.",
+            ));
+        }
+        for range in record
+            .exclude
+            .iter()
+            .chain(record.include.iter())
+            .chain(record.prefer.iter())
+            .chain(record.allow.iter())
+            .chain(record.deny.iter())
+        {
+            range.parse::<AddressRange>()?;
+        }
+        // 检查IP来源
+        for spec in &record.sources {
+            match spec.parse::<SourceSpec>()? {
+                SourceSpec::Http(Some(name))
+                    if !config.http_detect.sources.iter().any(|s| s.name == name) =>
+                {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("HTTP source \"{}\" is not configured.", name),
+                    ));
+                }
+                SourceSpec::Http(None) if config.http_detect.sources.is_empty() => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "No HTTP source is configured.",
+                    ));
+                }
+                SourceSpec::Stun if config.stun_detect.servers.is_empty() => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "No STUN server is configured.",
+                    ));
+                }
+                SourceSpec::Dns if config.dns_detect.queries.is_empty() => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "No DNS detect query is configured.",
+                    ));
+                }
+                SourceSpec::Command(name)
+                    if !config
+                        .command_detect
+                        .commands
+                        .iter()
+                        .any(|c| c.name == name) =>
+                {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Command source \"{}\" is not configured.", name),
+                    ));
+                }
+                SourceSpec::File(name)
+                    if !config.file_detect.files.iter().any(|f| f.name == name) =>
+                {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("File source \"{}\" is not configured.", name),
+                    ));
+                }
+                _ => (),
+            }
+        }
     }
 
     // 检查是否配置了认证ID和Token
@@ -195,6 +331,158 @@ fn check_config(config: &Config) -> Result<()> {
             "Recheck interval is invalid.",
         ));
     }
+    if config.check.confirm_count == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Confirm count is invalid.",
+        ));
+    }
+    if config.check.watch_config && config.check.watch_config_interval == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Watch config interval is invalid.",
+        ));
+    }
+
+    // 检查解析结果检查配置是否合法
+    if config.drift_check.enable && config.drift_check.timeout == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Drift check timeout is invalid.",
+        ));
+    }
+    if config.propagation.enable && config.propagation.poll_interval == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Propagation poll interval is invalid.",
+        ));
+    }
+
+    // 检查重试配置是否合法
+    if config.retry.max_attempts > 0
+        && (config.retry.initial_delay == 0 || config.retry.max_delay < config.retry.initial_delay)
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Retry delay is invalid.",
+        ));
+    }
+
+    // 检查HTTP检测配置是否合法
+    for source in &config.http_detect.sources {
+        if source.name.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "HTTP source name is empty.",
+            ));
+        }
+        if source.url_v4.is_none() && source.url_v6.is_none() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("HTTP source \"{}\" has no URL.", source.name),
+            ));
+        }
+    }
+    if !config.http_detect.sources.is_empty()
+        && config.http_detect.required_votes() > config.http_detect.sources.len()
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "HTTP quorum is larger than the number of sources.",
+        ));
+    }
+    if config.http_detect.timeout == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "HTTP timeout is invalid.",
+        ));
+    }
+    if config.stun_detect.timeout == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "STUN timeout is invalid.",
+        ));
+    }
+
+    // 检查DNS检测配置是否合法
+    for dns_query in &config.dns_detect.queries {
+        if dns_query.server.is_empty() || dns_query.name.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "DNS detect server or name is empty.",
+            ));
+        }
+        if !["A", "AAAA", "TXT"].contains(&dns_query.query_type.as_str()) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "DNS detect query type is invalid.",
+            ));
+        }
+    }
+    if config.dns_detect.timeout == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "DNS detect timeout is invalid.",
+        ));
+    }
+
+    // 检查网关检测配置是否合法
+    if let Some(gateway) = &config.gateway_detect.gateway {
+        if gateway.parse::<Ipv4Addr>().is_err() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Gateway address is invalid.",
+            ));
+        }
+    }
+    if config.gateway_detect.timeout == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Gateway detect timeout is invalid.",
+        ));
+    }
+
+    // 检查UDP Socket检测配置是否合法
+    if config
+        .socket_detect
+        .probe_v4
+        .parse::<SocketAddrV4>()
+        .is_err()
+        || config
+            .socket_detect
+            .probe_v6
+            .parse::<SocketAddrV6>()
+            .is_err()
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Socket probe address is invalid.",
+        ));
+    }
+
+    // 检查外部命令与文件检测配置是否合法
+    for command in &config.command_detect.commands {
+        if command.name.is_empty() || command.command.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Command source name or command is empty.",
+            ));
+        }
+    }
+    if config.command_detect.timeout == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Command detect timeout is invalid.",
+        ));
+    }
+    for file in &config.file_detect.files {
+        if file.name.is_empty() || file.path.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "File source name or path is empty.",
+            ));
+        }
+    }
 
     Ok(())
 }
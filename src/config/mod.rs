@@ -1,10 +1,16 @@
+pub mod handle;
+pub mod history;
 pub mod load_config;
 pub mod record;
+pub mod select;
+pub mod source;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, io::Result};
 
 use pnet::ipnetwork::IpNetwork;
+use select::AddressPolicy;
 use serde::{Deserialize, Serialize};
+use source::{NicSelector, SourceSpec};
 
 pub static LOG_PREFIX: &str = "aliyun-ddns";
 static DEFAULT_LOG_LEVEL: &str = "info";
@@ -22,22 +28,94 @@ impl PartialEq for IP {
     }
 }
 
+/// 解析记录更新后的生效情况
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Propagation {
+    /// 更新后的记录值
+    pub value: String,
+    /// 更新时间
+    pub updated_at: i64,
+    /// 确认所有服务器均返回新记录值的时间，超时未生效时为空
+    pub confirmed_at: Option<i64>,
+    /// 生效耗时（单位：毫秒）
+    pub latency_ms: Option<u64>,
+}
+
+/// 记录文件格式的版本
+pub static RECORD_VERSION: u32 = 2;
+
+/// 解析记录的发布状态
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RecordState {
+    pub domain: String,
+    pub hostname: String,
+    pub record_type: String,
+    /// 已发布的记录值
+    #[serde(default)]
+    pub value: Option<String>,
+    /// 阿里云解析记录ID
+    #[serde(default)]
+    pub record_id: Option<String>,
+    /// 最近一次成功发布的时间
+    #[serde(default)]
+    pub last_success: Option<i64>,
+    /// 最近一次更新失败的时间
+    #[serde(default)]
+    pub last_failure: Option<i64>,
+    /// 最近一次更新失败的原因
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// 累计尝试更新的次数
+    #[serde(default)]
+    pub attempts: u64,
+    /// 自上次成功后连续失败的次数
+    #[serde(default)]
+    pub failures: u32,
+    /// 最近一次更新的生效情况
+    #[serde(default)]
+    pub propagation: Option<Propagation>,
+}
+
 /// 记录上次的运行信息（单独存储于特定文件中）
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Record {
-    pub last_ip: HashMap<String, IP>,
+    /// 记录文件格式的版本，见 `RECORD_VERSION`
+    pub version: u32,
     pub last_check: i64,
     pub last_update: i64,
+    /// 各解析记录的状态，键为 `FQDN/记录类型`
+    #[serde(default)]
+    pub records: HashMap<String, RecordState>,
 }
 
 impl Record {
     fn new() -> Record {
         Record {
-            last_ip: HashMap::new(),
+            version: RECORD_VERSION,
             last_check: 0,
             last_update: 0,
+            records: HashMap::new(),
         }
     }
+
+    /// 解析记录的状态，不存在时创建
+    pub fn state(&mut self, domain: &str, hostname: &str, record_type: &str) -> &mut RecordState {
+        self.records
+            .entry(record_key(domain, hostname, record_type))
+            .or_insert_with(|| RecordState {
+                domain: domain.to_string(),
+                hostname: hostname.to_string(),
+                record_type: record_type.to_string(),
+                ..Default::default()
+            })
+    }
+
+    /// 解析记录已发布的记录值
+    pub fn published(&self, domain: &str, hostname: &str, record_type: &str) -> Option<&str> {
+        self.records
+            .get(&record_key(domain, hostname, record_type))
+            .and_then(|state| state.value.as_deref())
+    }
 }
 
 /// 配置信息
@@ -50,6 +128,28 @@ pub struct Config {
     pub auth: Auth,
     pub log: Log,
     pub check: Check,
+    #[serde(default = "DriftCheck::new", rename = "drift-check")]
+    pub drift_check: DriftCheck,
+    #[serde(default = "PropagationCheck::new", rename = "propagation")]
+    pub propagation: PropagationCheck,
+    #[serde(default = "Retry::new", rename = "retry")]
+    pub retry: Retry,
+    #[serde(default = "History::new", rename = "history")]
+    pub history: History,
+    #[serde(default = "HttpDetect::new", rename = "http-detect")]
+    pub http_detect: HttpDetect,
+    #[serde(default = "StunDetect::new", rename = "stun-detect")]
+    pub stun_detect: StunDetect,
+    #[serde(default = "DnsDetect::new", rename = "dns-detect")]
+    pub dns_detect: DnsDetect,
+    #[serde(default = "GatewayDetect::new", rename = "gateway-detect")]
+    pub gateway_detect: GatewayDetect,
+    #[serde(default = "CommandDetect::new", rename = "command-detect")]
+    pub command_detect: CommandDetect,
+    #[serde(default = "FileDetect::new", rename = "file-detect")]
+    pub file_detect: FileDetect,
+    #[serde(default = "SocketDetect::new", rename = "socket-detect")]
+    pub socket_detect: SocketDetect,
 }
 
 /// 关联的解析记录
@@ -61,6 +161,80 @@ pub struct MonitorRecord {
     pub hostname: String,
     #[serde(default = "empty", rename = "nic-name")]
     pub nic_name: Option<String>,
+    /// 按MAC地址选择网卡
+    #[serde(default = "empty", rename = "nic-mac")]
+    pub nic_mac: Option<String>,
+    /// 按名称匹配规则选择网卡：通配符或以 `re:` 开头的正则表达式
+    #[serde(default = "empty", rename = "nic-pattern")]
+    pub nic_pattern: Option<String>,
+    /// 使用默认路由所在的网卡
+    #[serde(default = "default_nic_default_route", rename = "nic-default-route")]
+    pub nic_default_route: bool,
+    /// 按顺序尝试的IP来源，前一个来源失败时使用下一个
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// 地址作用域：`any` 或 `global`
+    #[serde(default = "default_address_scope")]
+    pub scope: String,
+    /// 排除的地址范围
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// 仅使用这些范围内的地址，为空时不限制
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// 按顺序优先使用这些范围内的地址
+    #[serde(default)]
+    pub prefer: Vec<String>,
+    /// 发布前的地址检查中允许发布的地址范围，跳过默认的拒绝及警告规则
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// 发布前的地址检查中拒绝发布的地址范围，优先于 `allow`
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// 是否优先使用临时IPv6地址，未指定 `address-policy` 时有效
+    #[serde(default = "default_use_temporary_addr", rename = "use-temporary-addr")]
+    pub use_temporary_addr: bool,
+    /// 地址选择策略：`stable`、`temporary` 或 `longest-lifetime`
+    #[serde(default = "empty", rename = "address-policy")]
+    pub address_policy: Option<String>,
+    /// 前缀委派模式：以来源地址的前缀与该接口标识组合成记录值
+    #[serde(default = "empty")]
+    pub suffix: Option<String>,
+    /// 前缀委派模式下取来源地址的前多少位作为前缀
+    #[serde(default = "default_prefix_length", rename = "prefix-length")]
+    pub prefix_length: u8,
+    /// 新地址需连续检测到多少次才更新，未指定时使用 `[check]` 中的配置
+    #[serde(default, rename = "confirm-count")]
+    pub confirm_count: Option<u32>,
+    /// 新地址需保持多久不变才更新（单位：秒），未指定时使用 `[check]` 中的配置
+    #[serde(default, rename = "confirm-time")]
+    pub confirm_time: Option<u64>,
+}
+
+impl MonitorRecord {
+    /// 记录指定的网卡选择方式，未指定时返回 None
+    pub fn nic_selector(&self) -> Option<NicSelector> {
+        if let Some(nic_name) = &self.nic_name {
+            Some(NicSelector::Name(nic_name.clone()))
+        } else if let Some(nic_mac) = &self.nic_mac {
+            Some(NicSelector::Mac(nic_mac.clone()))
+        } else if let Some(nic_pattern) = &self.nic_pattern {
+            Some(NicSelector::Pattern(nic_pattern.clone()))
+        } else if self.nic_default_route {
+            Some(NicSelector::DefaultRoute)
+        } else {
+            None
+        }
+    }
+
+    /// 实际使用的地址选择策略
+    pub fn address_policy(&self) -> Result<AddressPolicy> {
+        match &self.address_policy {
+            Some(policy) => policy.parse(),
+            None if self.use_temporary_addr => Ok(AddressPolicy::Temporary),
+            None => Ok(AddressPolicy::Stable),
+        }
+    }
 }
 
 /// Authentication Info
@@ -75,7 +249,7 @@ pub struct Auth {
 }
 
 /// Log config
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Log {
     /// 是否启用日志文件
     #[serde(default = "default_file_log", rename = "log-to-file")]
@@ -102,9 +276,315 @@ pub struct Check {
     pub enable_recheck: bool,
     #[serde(default = "default_recheck_interval", rename = "recheck-interval")]
     pub recheck_interval: u64,
+    /// 启动时是否对照解析记录的实际值进行校正
+    #[serde(default = "default_reconcile_on_start", rename = "reconcile-on-start")]
+    pub reconcile_on_start: bool,
+    /// 每隔多少次检查对照解析记录的实际值进行校正，0为不进行
+    #[serde(default = "default_reconcile_interval", rename = "reconcile-interval")]
+    pub reconcile_interval: u64,
+    /// 是否监听网卡地址变化并立即检查（仅Linux）
+    #[serde(default = "default_watch_address", rename = "watch-address")]
+    pub watch_address: bool,
+    /// 地址变化后等待多久没有新的变化才检查（单位：秒）
+    #[serde(default = "default_watch_debounce", rename = "watch-debounce")]
+    pub watch_debounce: u64,
+    /// 新地址需连续检测到多少次才更新
+    #[serde(default = "default_confirm_count", rename = "confirm-count")]
+    pub confirm_count: u32,
+    /// 新地址需保持多久不变才更新（单位：秒）
+    #[serde(default = "default_confirm_time", rename = "confirm-time")]
+    pub confirm_time: u64,
+    /// 配置文件修改后是否自动重新加载
+    #[serde(default = "default_watch_config", rename = "watch-config")]
+    pub watch_config: bool,
+    /// 检查配置文件是否修改的间隔（单位：秒）
+    #[serde(
+        default = "default_watch_config_interval",
+        rename = "watch-config-interval"
+    )]
+    pub watch_config_interval: u64,
+    /// 是否创建控制套接字，用于通过命令立即触发检查
+    #[serde(default = "default_control_socket", rename = "control-socket")]
+    pub control_socket: bool,
+}
+
+/// Drift check config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DriftCheck {
+    /// 是否直接查询权威DNS服务器，检查实际的解析结果
+    #[serde(default = "default_drift_check", rename = "enable")]
+    pub enable: bool,
+    /// 每隔多少次检查进行一次，0为仅在更新解析记录后进行
+    #[serde(default = "default_drift_interval", rename = "interval")]
+    pub interval: u64,
+    /// 更新解析记录后等待多久再检查（单位：秒）
+    #[serde(default = "default_drift_update_delay", rename = "update-delay")]
+    pub update_delay: u64,
+    /// 权威DNS服务器，为空时通过NS记录查找
+    #[serde(default, rename = "nameservers")]
+    pub nameservers: Vec<String>,
+    /// 用于查找NS记录的DNS服务器，为空时使用系统配置
+    #[serde(default = "empty_string", rename = "resolver")]
+    pub resolver: String,
+    /// 是否使用TCP查询
+    #[serde(default = "default_drift_use_tcp", rename = "use-tcp")]
+    pub use_tcp: bool,
+    /// 单次查询的超时时间（单位：秒）
+    #[serde(default = "default_drift_timeout", rename = "timeout")]
+    pub timeout: u64,
+}
+
+/// Propagation check config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PropagationCheck {
+    /// 更新解析记录后是否等待新的记录值生效
+    #[serde(default = "default_propagation_check", rename = "enable")]
+    pub enable: bool,
+    /// 等待生效的超时时间（单位：秒）
+    #[serde(default = "default_propagation_timeout", rename = "timeout")]
+    pub timeout: u64,
+    /// 轮询间隔（单位：秒）
+    #[serde(
+        default = "default_propagation_poll_interval",
+        rename = "poll-interval"
+    )]
+    pub poll_interval: u64,
+    /// 除权威DNS服务器外，额外轮询的公共DNS
+    #[serde(default, rename = "public-resolvers")]
+    pub public_resolvers: Vec<String>,
+}
+
+/// Retry config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Retry {
+    /// 更新失败的记录最多重试多少次，0为不重试
+    #[serde(default = "default_retry_max_attempts", rename = "max-attempts")]
+    pub max_attempts: u32,
+    /// 首次重试前的等待时间（单位：秒），之后每次翻倍
+    #[serde(default = "default_retry_initial_delay", rename = "initial-delay")]
+    pub initial_delay: u64,
+    /// 重试前的最长等待时间（单位：秒）
+    #[serde(default = "default_retry_max_delay", rename = "max-delay")]
+    pub max_delay: u64,
+    /// 网络错误（超时、连接失败）时立即重试的次数
+    #[serde(default = "default_retry_immediate", rename = "immediate-retries")]
+    pub immediate_retries: u32,
+}
+
+/// History config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct History {
+    /// 是否记录变更历史
+    #[serde(default = "default_history", rename = "enable")]
+    pub enable: bool,
+    /// 历史保留的天数，0为不限制
+    #[serde(default = "default_history_max_age", rename = "max-age")]
+    pub max_age: u64,
+    /// 历史保留的最大条数，0为不限制
+    #[serde(default = "default_history_max_entries", rename = "max-entries")]
+    pub max_entries: usize,
+}
+
+/// HTTP detect config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpDetect {
+    /// 至少需要多少个来源返回相同的地址才采纳，0为超过半数
+    #[serde(default = "default_http_quorum", rename = "quorum")]
+    pub quorum: usize,
+    /// 单次请求的超时时间（单位：秒）
+    #[serde(default = "default_http_timeout", rename = "timeout")]
+    pub timeout: u64,
+    /// 查询公网IP的HTTP服务
+    #[serde(default, rename = "source")]
+    pub sources: Vec<HttpSource>,
+}
+
+/// 查询公网IP的HTTP服务
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpSource {
+    #[serde(default = "empty_string")]
+    pub name: String,
+    /// 获取IPv4地址的URL
+    #[serde(default = "empty", rename = "url-v4")]
+    pub url_v4: Option<String>,
+    /// 获取IPv6地址的URL
+    #[serde(default = "empty", rename = "url-v6")]
+    pub url_v6: Option<String>,
+    /// 返回JSON时，地址所在的字段（以`.`分隔多级字段），为空时将返回内容视为纯文本
+    #[serde(default = "empty", rename = "json-field")]
+    pub json_field: Option<String>,
+}
+
+/// STUN detect config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StunDetect {
+    /// STUN服务器（`主机名[:端口]`），按顺序尝试
+    #[serde(default, rename = "servers")]
+    pub servers: Vec<String>,
+    /// 单次请求的超时时间（单位：秒）
+    #[serde(default = "default_stun_timeout", rename = "timeout")]
+    pub timeout: u64,
+}
+
+/// DNS detect config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DnsDetect {
+    /// 查询公网IP的DNS请求，按顺序尝试
+    #[serde(default, rename = "query")]
+    pub queries: Vec<DnsDetectQuery>,
+    /// 单次查询的超时时间（单位：秒）
+    #[serde(default = "default_dns_detect_timeout", rename = "timeout")]
+    pub timeout: u64,
+}
+
+/// 查询公网IP的DNS请求
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DnsDetectQuery {
+    /// DNS服务器（IP、IP:端口或主机名）
+    #[serde(default = "empty_string")]
+    pub server: String,
+    /// 查询的域名
+    #[serde(default = "empty_string")]
+    pub name: String,
+    /// 查询的记录类型：`A`、`AAAA`或`TXT`
+    #[serde(default = "empty_string", rename = "type")]
+    pub query_type: String,
+}
+
+/// Gateway detect config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GatewayDetect {
+    /// 是否通过网关（UPnP IGD / NAT-PMP / PCP）查询WAN口地址
+    #[serde(default = "default_gateway_detect", rename = "enable")]
+    pub enable: bool,
+    /// NAT-PMP / PCP 使用的网关地址，为空时使用默认路由的网关
+    #[serde(default = "empty", rename = "gateway")]
+    pub gateway: Option<String>,
+    /// NAT-PMP 失败后是否使用PCP，PCP需在网关上创建一个短时的端口映射
+    #[serde(default = "default_gateway_pcp", rename = "pcp")]
+    pub pcp: bool,
+    /// 单次请求的超时时间（单位：秒）
+    #[serde(default = "default_gateway_timeout", rename = "timeout")]
+    pub timeout: u64,
+}
+
+/// Command detect config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandDetect {
+    /// 单次执行的超时时间（单位：秒），超时后终止命令
+    #[serde(default = "default_command_timeout", rename = "timeout")]
+    pub timeout: u64,
+    /// 输出公网IP的外部命令
+    #[serde(default, rename = "command")]
+    pub commands: Vec<CommandSource>,
+}
+
+/// 输出公网IP的外部命令
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandSource {
+    #[serde(default = "empty_string")]
+    pub name: String,
+    /// 可执行文件路径（不经过Shell）
+    #[serde(default = "empty_string")]
+    pub command: String,
+    /// 命令参数
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// File detect config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileDetect {
+    /// 文件超过多久未修改即视为过期（单位：秒），0为不检查
+    #[serde(default = "default_file_max_age", rename = "max-age")]
+    pub max_age: u64,
+    /// 由其他程序写入公网IP的文件
+    #[serde(default, rename = "file")]
+    pub files: Vec<FileSource>,
+}
+
+/// 由其他程序写入公网IP的文件
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileSource {
+    #[serde(default = "empty_string")]
+    pub name: String,
+    #[serde(default = "empty_string")]
+    pub path: String,
+}
+
+/// Socket detect config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SocketDetect {
+    /// 是否以主路由表中默认路由的出口地址代替UDP Socket
+    #[serde(default = "default_socket_use_route", rename = "use-route")]
+    pub use_route: bool,
+    /// 获取IPv4地址时UDP Socket连接的目标（不会发送数据）
+    #[serde(default = "default_socket_probe_v4", rename = "probe-v4")]
+    pub probe_v4: String,
+    /// 获取IPv6地址时UDP Socket连接的目标（不会发送数据）
+    #[serde(default = "default_socket_probe_v6", rename = "probe-v6")]
+    pub probe_v6: String,
 }
 
 impl Config {
+    /// 主机记录对应的完整域名
+    pub fn fqdn(&self, hostname: &str) -> String {
+        fqdn(hostname, &self.domain_name)
+    }
+
+    /// 记录实际使用的IP来源
+    ///
+    /// 未配置 `sources` 时：指定了网卡（`nic-name`、`nic-mac`、`nic-pattern`、`nic-default-route`）则使用该网卡，
+    /// 否则依次使用已配置的HTTP服务、STUN服务器、DNS查询、网关，最后使用UDP Socket（或默认路由）
+    pub fn record_sources(&self, record: &MonitorRecord) -> Vec<SourceSpec> {
+        if !record.sources.is_empty() {
+            return record
+                .sources
+                .iter()
+                .filter_map(|spec| spec.parse().ok())
+                .collect();
+        }
+        if let Some(selector) = record.nic_selector() {
+            return vec![SourceSpec::Nic(selector)];
+        }
+
+        let mut sources = Vec::new();
+        if !self.http_detect.sources.is_empty() {
+            sources.push(SourceSpec::Http(None));
+        }
+        if !self.stun_detect.servers.is_empty() {
+            sources.push(SourceSpec::Stun);
+        }
+        if !self.dns_detect.queries.is_empty() {
+            sources.push(SourceSpec::Dns);
+        }
+        if self.gateway_detect.enable {
+            sources.push(SourceSpec::Upnp);
+        }
+        if self.socket_detect.use_route {
+            sources.push(SourceSpec::Route);
+        } else {
+            sources.push(SourceSpec::Socket);
+        }
+        sources
+    }
+
+    /// 记录实际使用的变化确认策略：(连续检测次数, 保持不变的秒数)
+    ///
+    /// 启用 `enable-recheck` 时至少连续检测到两次
+    pub fn record_confirmation(&self, record: &MonitorRecord) -> (u32, u64) {
+        let count = record.confirm_count.unwrap_or(self.check.confirm_count);
+        let count = if self.check.enable_recheck {
+            count.max(2)
+        } else {
+            count
+        };
+        (
+            count,
+            record.confirm_time.unwrap_or(self.check.confirm_time),
+        )
+    }
+
     fn new() -> Config {
         Config {
             domain_name: empty_string(),
@@ -112,6 +592,17 @@ impl Config {
             auth: Auth::new(),
             log: Log::new(),
             check: Check::new(),
+            drift_check: DriftCheck::new(),
+            propagation: PropagationCheck::new(),
+            retry: Retry::new(),
+            history: History::new(),
+            http_detect: HttpDetect::new(),
+            stun_detect: StunDetect::new(),
+            dns_detect: DnsDetect::new(),
+            gateway_detect: GatewayDetect::new(),
+            command_detect: CommandDetect::new(),
+            file_detect: FileDetect::new(),
+            socket_detect: SocketDetect::new(),
         }
     }
 }
@@ -151,10 +642,154 @@ impl Check {
             check_interval: default_check_interval(),
             enable_recheck: default_recheck(),
             recheck_interval: default_recheck_interval(),
+            reconcile_on_start: default_reconcile_on_start(),
+            reconcile_interval: default_reconcile_interval(),
+            watch_address: default_watch_address(),
+            watch_debounce: default_watch_debounce(),
+            confirm_count: default_confirm_count(),
+            confirm_time: default_confirm_time(),
+            watch_config: default_watch_config(),
+            watch_config_interval: default_watch_config_interval(),
+            control_socket: default_control_socket(),
+        }
+    }
+}
+
+impl DriftCheck {
+    fn new() -> DriftCheck {
+        DriftCheck {
+            enable: default_drift_check(),
+            interval: default_drift_interval(),
+            update_delay: default_drift_update_delay(),
+            nameservers: Vec::new(),
+            resolver: empty_string(),
+            use_tcp: default_drift_use_tcp(),
+            timeout: default_drift_timeout(),
+        }
+    }
+}
+
+impl PropagationCheck {
+    fn new() -> PropagationCheck {
+        PropagationCheck {
+            enable: default_propagation_check(),
+            timeout: default_propagation_timeout(),
+            poll_interval: default_propagation_poll_interval(),
+            public_resolvers: Vec::new(),
+        }
+    }
+}
+
+impl Retry {
+    fn new() -> Retry {
+        Retry {
+            max_attempts: default_retry_max_attempts(),
+            initial_delay: default_retry_initial_delay(),
+            max_delay: default_retry_max_delay(),
+            immediate_retries: default_retry_immediate(),
+        }
+    }
+}
+
+impl History {
+    fn new() -> History {
+        History {
+            enable: default_history(),
+            max_age: default_history_max_age(),
+            max_entries: default_history_max_entries(),
+        }
+    }
+}
+
+impl HttpDetect {
+    fn new() -> HttpDetect {
+        HttpDetect {
+            quorum: default_http_quorum(),
+            timeout: default_http_timeout(),
+            sources: Vec::new(),
+        }
+    }
+
+    /// 实际需要的一致来源数
+    pub fn required_votes(&self) -> usize {
+        if self.quorum == 0 {
+            self.sources.len() / 2 + 1
+        } else {
+            self.quorum
+        }
+    }
+}
+
+impl StunDetect {
+    fn new() -> StunDetect {
+        StunDetect {
+            servers: Vec::new(),
+            timeout: default_stun_timeout(),
+        }
+    }
+}
+
+impl DnsDetect {
+    fn new() -> DnsDetect {
+        DnsDetect {
+            queries: Vec::new(),
+            timeout: default_dns_detect_timeout(),
+        }
+    }
+}
+
+impl GatewayDetect {
+    fn new() -> GatewayDetect {
+        GatewayDetect {
+            enable: default_gateway_detect(),
+            gateway: empty(),
+            pcp: default_gateway_pcp(),
+            timeout: default_gateway_timeout(),
+        }
+    }
+}
+
+impl CommandDetect {
+    fn new() -> CommandDetect {
+        CommandDetect {
+            timeout: default_command_timeout(),
+            commands: Vec::new(),
         }
     }
 }
 
+impl FileDetect {
+    fn new() -> FileDetect {
+        FileDetect {
+            max_age: default_file_max_age(),
+            files: Vec::new(),
+        }
+    }
+}
+
+impl SocketDetect {
+    fn new() -> SocketDetect {
+        SocketDetect {
+            use_route: default_socket_use_route(),
+            probe_v4: default_socket_probe_v4(),
+            probe_v6: default_socket_probe_v6(),
+        }
+    }
+}
+
+/// 记录文件中解析记录的键：`FQDN/记录类型`
+fn record_key(domain: &str, hostname: &str, record_type: &str) -> String {
+    format!("{}/{}", fqdn(hostname, domain), record_type)
+}
+
+fn fqdn(hostname: &str, domain_name: &str) -> String {
+    if hostname == "@" {
+        domain_name.to_string()
+    } else {
+        format!("{}.{}", hostname, domain_name)
+    }
+}
+
 fn empty_string() -> String {
     String::new()
 }
@@ -179,3 +814,143 @@ fn default_recheck() -> bool {
 fn default_recheck_interval() -> u64 {
     5
 }
+fn default_reconcile_on_start() -> bool {
+    false
+}
+fn default_reconcile_interval() -> u64 {
+    0
+}
+fn default_watch_address() -> bool {
+    false
+}
+fn default_watch_debounce() -> u64 {
+    2
+}
+fn default_watch_config() -> bool {
+    false
+}
+fn default_watch_config_interval() -> u64 {
+    10
+}
+fn default_control_socket() -> bool {
+    false
+}
+fn default_confirm_count() -> u32 {
+    1
+}
+fn default_confirm_time() -> u64 {
+    0
+}
+fn default_drift_check() -> bool {
+    false
+}
+fn default_drift_interval() -> u64 {
+    1
+}
+fn default_drift_update_delay() -> u64 {
+    5
+}
+fn default_drift_use_tcp() -> bool {
+    false
+}
+fn default_drift_timeout() -> u64 {
+    3
+}
+fn default_propagation_check() -> bool {
+    false
+}
+fn default_propagation_timeout() -> u64 {
+    300
+}
+fn default_propagation_poll_interval() -> u64 {
+    5
+}
+fn default_retry_max_attempts() -> u32 {
+    5
+}
+fn default_retry_initial_delay() -> u64 {
+    10
+}
+fn default_retry_max_delay() -> u64 {
+    600
+}
+fn default_retry_immediate() -> u32 {
+    1
+}
+fn default_history() -> bool {
+    true
+}
+fn default_history_max_age() -> u64 {
+    365
+}
+fn default_history_max_entries() -> usize {
+    10000
+}
+fn default_http_quorum() -> usize {
+    0
+}
+fn default_http_timeout() -> u64 {
+    5
+}
+fn default_stun_timeout() -> u64 {
+    3
+}
+fn default_dns_detect_timeout() -> u64 {
+    3
+}
+fn default_gateway_detect() -> bool {
+    false
+}
+fn default_gateway_pcp() -> bool {
+    false
+}
+fn default_gateway_timeout() -> u64 {
+    3
+}
+fn default_command_timeout() -> u64 {
+    10
+}
+fn default_file_max_age() -> u64 {
+    0
+}
+fn default_address_scope() -> String {
+    String::from("any")
+}
+fn default_use_temporary_addr() -> bool {
+    false
+}
+fn default_prefix_length() -> u8 {
+    64
+}
+fn default_nic_default_route() -> bool {
+    false
+}
+fn default_socket_use_route() -> bool {
+    false
+}
+fn default_socket_probe_v4() -> String {
+    String::from("8.8.8.8:80")
+}
+fn default_socket_probe_v6() -> String {
+    String::from("[2001:4860:4860::8888]:80")
+}
+
+#[cfg(test)]
+impl Config {
+    /// 测试用的配置，域名为 `example.com`
+    ///
+    /// # Arguments
+    ///     records: `[[record]]` 部分的TOML
+    pub fn for_test(records: &str) -> Config {
+        #[derive(Deserialize)]
+        struct Records {
+            #[serde(default)]
+            record: Vec<MonitorRecord>,
+        }
+
+        let mut config = Config::new();
+        config.domain_name = String::from("example.com");
+        config.records = toml::from_str::<Records>(records).unwrap().record;
+        config
+    }
+}
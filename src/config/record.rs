@@ -1,94 +1,472 @@
 use std::{
-    fs::{File, OpenOptions},
-    io::{BufReader, Result, Write},
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufReader, Error, ErrorKind, Result, Write},
+    path::{Path, PathBuf},
 };
 
-use tracing::warn;
+use serde::Deserialize;
+use tracing::{info, warn};
 
-use super::Record;
+use super::{Config, Propagation, Record, IP, RECORD_VERSION};
+
+/// 第1版（无版本号）的记录文件，以网卡名称（未指定网卡时为空字符串）或 `FQDN/记录类型` 为键保存上次的地址
+#[derive(Deserialize)]
+struct RecordV1 {
+    last_ip: HashMap<String, IP>,
+    last_check: i64,
+    last_update: i64,
+    #[serde(default)]
+    propagation: HashMap<String, Propagation>,
+}
 
 pub struct Recorder {
-    record_file_path: String,
+    record_file_path: PathBuf,
     record: Record,
+    /// 记录目录的锁，持有期间其他实例无法使用同一记录目录
+    _lock_file: File,
 }
 
 impl Recorder {
-    pub fn new(record_file_dir: String) -> Self {
-        let record_file_path = format!("{}/record.json", record_file_dir);
-        // 如果文件不存在，创建一个新的，并写入默认数据
-        // 如果文件存在，读取文件内容
-        let record: Record;
+    /// 锁定记录目录并读取记录文件
+    ///
+    /// 记录文件不存在时创建；无法解析时改用备份文件（`record.json.bak`），备份也无法使用时重置为默认值，
+    /// 无法解析的文件另存为 `record.json.invalid`
+    pub fn new(record_file_dir: String, config: &Config) -> Result<Self> {
+        let record_file_dir = Path::new(&record_file_dir);
+        let lock_file = Self::lock(record_file_dir)?;
+        let record_file_path = record_file_dir.join("record.json");
+        // 上次写入中断时遗留的临时文件
+        let _ = fs::remove_file(record_file_path.with_extension("json.tmp"));
+
+        let (record, need_save) = if !record_file_path.exists() {
+            (Record::new(), true)
+        } else {
+            match Self::read_from_file(&record_file_path, config) {
+                Ok(result) => result,
+                Err(e) => {
+                    warn!("Record data is invalid: {}", e);
+                    let invalid_path = record_file_path.with_extension("json.invalid");
+                    fs::rename(&record_file_path, &invalid_path)?;
+                    warn!("Invalid record file moved to {}", invalid_path.display());
+
+                    let backup_path = record_file_path.with_extension("json.bak");
+                    match Self::read_from_file(&backup_path, config) {
+                        Ok((record, _)) => {
+                            warn!("Record restored from backup {}", backup_path.display());
+                            (record, true)
+                        }
+                        Err(_) => {
+                            warn!("No usable backup, reset record to default.");
+                            (Record::new(), true)
+                        }
+                    }
+                }
+            }
+        };
 
-        let mut record_file = match OpenOptions::new()
+        let recorder = Recorder {
+            record_file_path,
+            record,
+            _lock_file: lock_file,
+        };
+        if need_save {
+            recorder.save()?;
+        }
+        Ok(recorder)
+    }
+
+    /// 锁定记录目录（`record.lock`），防止多个实例同时使用同一记录目录
+    ///
+    /// 仅Unix系统支持，锁在进程退出时自动释放
+    fn lock(record_file_dir: &Path) -> Result<File> {
+        let lock_path = record_file_dir.join("record.lock");
+        let lock_file = OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
-            .open(&record_file_path)
-        {
-            Ok(f) => f,
-            Err(_) => {
-                panic!("Failed to open record file: {}", record_file_path);
-            }
-        };
+            .truncate(false)
+            .open(&lock_path)
+            .map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!("Failed to open lock file {}: {}", lock_path.display(), e),
+                )
+            })?;
 
+        #[cfg(unix)]
         {
-            let result = Self::read_from_file(&record_file);
-            record = match result {
-                Ok(r) => r,
-                Err(_) => {
-                    // 文件内容无法解析，重置为默认值
-                    warn!("Record data is invalid, reset to default.");
-                    let record = Record::new();
-                    Self::write_to_file(&mut record_file, &record).unwrap();
-                    record
+            use std::{io::Read, os::unix::io::AsRawFd};
+
+            if unsafe { libc::flock(lock_file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+                let e = Error::last_os_error();
+                if e.kind() != ErrorKind::WouldBlock {
+                    return Err(e);
                 }
-            };
+                let mut pid = String::new();
+                let _ = (&lock_file).read_to_string(&mut pid);
+                return Err(Error::new(
+                    ErrorKind::WouldBlock,
+                    format!(
+                        "Record directory {} is in use by another instance (pid {})",
+                        record_file_dir.display(),
+                        pid.trim()
+                    ),
+                ));
+            }
+            // 写入进程号，便于排查
+            lock_file.set_len(0)?;
+            (&lock_file).write_all(std::process::id().to_string().as_bytes())?;
         }
+        Ok(lock_file)
+    }
 
-        Recorder {
-            record_file_path,
-            record,
+    /// 读取记录文件，旧版本的记录文件将被转换为当前版本
+    ///
+    /// # Return
+    ///     Result<(Record, bool)>: 记录内容，以及是否进行了转换
+    fn read_from_file(path: &Path, config: &Config) -> Result<(Record, bool)> {
+        let file = File::open(path)?;
+        let value: serde_json::Value =
+            serde_json::from_reader(BufReader::new(file)).map_err(Self::parse_error)?;
+        let version = value
+            .get("version")
+            .and_then(|version| version.as_u64())
+            .unwrap_or(1);
+
+        match version {
+            1 => {
+                let legacy: RecordV1 = serde_json::from_value(value).map_err(Self::parse_error)?;
+                info!(
+                    "Migrating record file from version 1 to version {}",
+                    RECORD_VERSION
+                );
+                Ok((Self::migrate_v1(legacy, config), true))
+            }
+            version if version == RECORD_VERSION as u64 => Ok((
+                serde_json::from_value(value).map_err(Self::parse_error)?,
+                false,
+            )),
+            version => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Record file version {} is not supported, the latest supported version is {}",
+                    version, RECORD_VERSION
+                ),
+            )),
         }
     }
 
-    fn read_from_file(file: &File) -> Result<Record> {
-        // Check if file exist
-        let record: Record;
+    /// 将第1版的记录转换为按解析记录保存的状态
+    ///
+    /// 第1版无法得知地址对应的解析记录，按当前配置中各记录的键（或其网卡名称）查找
+    fn migrate_v1(mut legacy: RecordV1, config: &Config) -> Record {
+        let mut record = Record::new();
+        record.last_check = legacy.last_check;
+        record.last_update = legacy.last_update;
 
-        match serde_json::from_reader(BufReader::new(file)) {
-            Ok(r) => record = r,
-            Err(e) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("Failed to parse record file: {}", e),
-                ));
+        for monitor_record in &config.records {
+            let key = format!(
+                "{}/{}",
+                config.fqdn(&monitor_record.hostname),
+                monitor_record.record_type
+            );
+            let ips = legacy.last_ip.get(&key).or_else(|| {
+                legacy
+                    .last_ip
+                    .get(&monitor_record.nic_name.clone().unwrap_or_default())
+            });
+            let value = ips
+                .and_then(|ips| {
+                    if monitor_record.record_type == "A" {
+                        ips.v4
+                    } else {
+                        ips.v6
+                    }
+                })
+                .map(|ip| ip.ip().to_string());
+            let propagation = legacy.propagation.remove(&key);
+            if value.is_none() && propagation.is_none() {
+                continue;
             }
+
+            let state = record.state(
+                &config.domain_name,
+                &monitor_record.hostname,
+                &monitor_record.record_type,
+            );
+            state.last_success = value
+                .as_ref()
+                .filter(|_| legacy.last_update > 0)
+                .map(|_| legacy.last_update);
+            state.value = value;
+            state.propagation = propagation;
         }
+        record
+    }
 
-        Ok(record)
+    fn parse_error(e: serde_json::Error) -> Error {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Failed to parse record file: {}", e),
+        )
     }
 
-    fn write_to_file(file: &mut File, record: &Record) -> Result<()> {
-        file.write_all(serde_json::to_string(record)?.as_bytes())
+    /// 写入记录文件
+    ///
+    /// 先写入临时文件并同步到磁盘，再以重命名替换原文件，写入过程中崩溃不会损坏原文件；
+    /// 替换前原文件保留为备份（`record.json.bak`）
+    fn save(&self) -> Result<()> {
+        let temp_path = self.record_file_path.with_extension("json.tmp");
+        {
+            let mut temp_file = File::create(&temp_path)?;
+            temp_file.write_all(serde_json::to_string(&self.record)?.as_bytes())?;
+            temp_file.sync_all()?;
+        }
+
+        if self.record_file_path.exists() {
+            let backup_path = self.record_file_path.with_extension("json.bak");
+            let _ = fs::remove_file(&backup_path);
+            if fs::hard_link(&self.record_file_path, &backup_path).is_err() {
+                fs::copy(&self.record_file_path, &backup_path)?;
+            }
+        }
+        fs::rename(&temp_path, &self.record_file_path)?;
+
+        // 同步目录，确保重命名已写入磁盘
+        #[cfg(unix)]
+        if let Some(dir) = self.record_file_path.parent() {
+            if let Ok(dir) = File::open(dir) {
+                let _ = dir.sync_all();
+            }
+        }
+        Ok(())
     }
 
     pub fn get_record(&self) -> Record {
         self.record.clone()
     }
 
+    /// 更新记录并写入记录文件，写入失败时仅给出警告
     pub fn update_record(&mut self, record: Record) {
         self.record = record;
-        let mut record_file = match OpenOptions::new()
-            .write(true)
-            .create(true)
-            .append(false)
-            .open(&self.record_file_path)
-        {
-            Ok(f) => f,
-            Err(_) => {
-                panic!("Failed to open record file: {}", &self.record_file_path);
+        if let Err(e) = self.save() {
+            warn!(
+                "Failed to save record file {}: {}",
+                self.record_file_path.display(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    /// 配置中的记录，每项为 (记录类型, 主机记录, 网卡名称)
+    fn config(records: &[(&str, &str, Option<&str>)]) -> Config {
+        let mut config = Config::new();
+        config.domain_name = String::from("example.com");
+        config.records = records
+            .iter()
+            .map(|(record_type, hostname, nic_name)| {
+                let mut text = format!(
+                    "record-type = \"{}\"\nhostname = \"{}\"\n",
+                    record_type, hostname
+                );
+                if let Some(nic_name) = nic_name {
+                    text.push_str(&format!("nic-name = \"{}\"\n", nic_name));
+                }
+                toml::from_str(&text).unwrap()
+            })
+            .collect();
+        config
+    }
+
+    fn migrate(json: &str, config: &Config) -> Record {
+        Recorder::migrate_v1(serde_json::from_str(json).unwrap(), config)
+    }
+
+    fn value<'a>(record: &'a Record, key: &str) -> Option<&'a str> {
+        record
+            .records
+            .get(key)
+            .and_then(|state| state.value.as_deref())
+    }
+
+    #[test]
+    fn migrate_v1_by_nic_name() {
+        // 第1版以网卡名称为键，未指定网卡时为空字符串
+        let json = r#"{
+            "last_ip": {
+                "eth0": {"v4": "203.0.113.5/24", "v6": "2001:db8::5/64"},
+                "": {"v4": "198.51.100.1/32", "v6": null}
+            },
+            "last_check": 1700000100,
+            "last_update": 1700000000
+        }"#;
+        let config = config(&[
+            ("A", "www", Some("eth0")),
+            ("AAAA", "www", Some("eth0")),
+            ("A", "@", None),
+            ("AAAA", "api", None),
+            ("A", "vpn", Some("wg0")),
+        ]);
+        let record = migrate(json, &config);
+
+        assert_eq!(record.version, RECORD_VERSION);
+        assert_eq!(
+            (record.last_check, record.last_update),
+            (1700000100, 1700000000)
+        );
+        assert_eq!(value(&record, "www.example.com/A"), Some("203.0.113.5"));
+        assert_eq!(value(&record, "www.example.com/AAAA"), Some("2001:db8::5"));
+        assert_eq!(value(&record, "example.com/A"), Some("198.51.100.1"));
+        // 没有对应地址的记录不产生状态
+        assert!(!record.records.contains_key("api.example.com/AAAA"));
+        assert!(!record.records.contains_key("vpn.example.com/A"));
+        assert_eq!(record.records.len(), 3);
+
+        let state = &record.records["www.example.com/A"];
+        assert_eq!(
+            (
+                state.domain.as_str(),
+                state.hostname.as_str(),
+                state.record_type.as_str()
+            ),
+            ("example.com", "www", "A")
+        );
+        assert_eq!(state.last_success, Some(1700000000));
+        assert_eq!(state.record_id, None);
+    }
+
+    #[test]
+    fn migrate_v1_by_record_key() {
+        // 以 `FQDN/记录类型` 为键的地址优先于网卡名称，生效情况随之迁移
+        let json = r#"{
+            "last_ip": {
+                "www.example.com/A": {"v4": "203.0.113.9/32", "v6": null},
+                "": {"v4": "198.51.100.1/32", "v6": null}
+            },
+            "last_check": 1700000100,
+            "last_update": 0,
+            "propagation": {
+                "www.example.com/A": {
+                    "value": "203.0.113.9",
+                    "updated_at": 1699999000,
+                    "confirmed_at": 1699999030,
+                    "latency_ms": 30000
+                }
             }
-        };
-        Self::write_to_file(&mut record_file, &self.record).unwrap();
+        }"#;
+        let record = migrate(json, &config(&[("A", "www", None), ("A", "mail", None)]));
+
+        let state = &record.records["www.example.com/A"];
+        assert_eq!(state.value.as_deref(), Some("203.0.113.9"));
+        // 从未更新过时没有成功时间
+        assert_eq!(state.last_success, None);
+        let propagation = state.propagation.as_ref().unwrap();
+        assert_eq!(propagation.value, "203.0.113.9");
+        assert_eq!(propagation.confirmed_at, Some(1699999030));
+        assert_eq!(value(&record, "mail.example.com/A"), Some("198.51.100.1"));
+    }
+
+    #[test]
+    fn migrate_v1_file_on_load() {
+        let dir = TempDir::new("record");
+        let path = dir.path().join("record.json");
+        fs::write(
+            &path,
+            r#"{"last_ip":{"":{"v4":"198.51.100.1/32","v6":null}},"last_check":2,"last_update":1}"#,
+        )
+        .unwrap();
+
+        let recorder =
+            Recorder::new(dir.to_str().to_string(), &config(&[("A", "www", None)])).unwrap();
+        assert_eq!(
+            value(&recorder.get_record(), "www.example.com/A"),
+            Some("198.51.100.1")
+        );
+
+        // 转换后以当前版本保存，原文件保留为备份
+        let saved: Record = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.version, RECORD_VERSION);
+        assert_eq!(value(&saved, "www.example.com/A"), Some("198.51.100.1"));
+        let backup = fs::read_to_string(path.with_extension("json.bak")).unwrap();
+        assert!(backup.contains("last_ip"));
+    }
+
+    /// 保存一条记录后释放记录目录
+    fn save_value(dir: &TempDir, config: &Config, ip: &str) {
+        let mut recorder = Recorder::new(dir.to_str().to_string(), config).unwrap();
+        let mut record = recorder.get_record();
+        record.state("example.com", "www", "A").value = Some(ip.to_string());
+        recorder.update_record(record);
+    }
+
+    #[test]
+    fn corrupt_record_falls_back_to_backup() {
+        let dir = TempDir::new("record");
+        let config = config(&[("A", "www", None)]);
+        save_value(&dir, &config, "192.0.2.1");
+        save_value(&dir, &config, "192.0.2.2");
+        // 没有遗留临时文件
+        assert!(!dir.path().join("record.json.tmp").exists());
+
+        let path = dir.path().join("record.json");
+        fs::write(&path, "{\"version\": 2, \"last_").unwrap();
+        let recorder = Recorder::new(dir.to_str().to_string(), &config).unwrap();
+        // 备份为上一次保存前的内容
+        assert_eq!(
+            value(&recorder.get_record(), "www.example.com/A"),
+            Some("192.0.2.1")
+        );
+        assert_eq!(
+            fs::read_to_string(path.with_extension("json.invalid")).unwrap(),
+            "{\"version\": 2, \"last_"
+        );
+        // 恢复的记录已重新写入
+        let saved: Record = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(value(&saved, "www.example.com/A"), Some("192.0.2.1"));
+    }
+
+    #[test]
+    fn unreadable_record_without_backup_is_reset() {
+        let dir = TempDir::new("record");
+        let path = dir.path().join("record.json");
+        fs::write(&path, "not json").unwrap();
+        // 备份同样无法使用
+        fs::write(path.with_extension("json.bak"), "{\"version\": 99}").unwrap();
+
+        let recorder = Recorder::new(dir.to_str().to_string(), &config(&[])).unwrap();
+        let record = recorder.get_record();
+        assert!(record.records.is_empty());
+        assert_eq!(record.last_check, 0);
+        assert_eq!(
+            fs::read_to_string(path.with_extension("json.invalid")).unwrap(),
+            "not json"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn second_lock_fails_with_pid() {
+        let dir = TempDir::new("record");
+        let lock = Recorder::lock(dir.path()).unwrap();
+
+        let e = Recorder::lock(dir.path()).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+        assert!(
+            e.to_string()
+                .contains(&format!("(pid {})", std::process::id())),
+            "{}",
+            e
+        );
+
+        // 锁释放后可再次锁定
+        drop(lock);
+        assert!(Recorder::lock(dir.path()).is_ok());
     }
 }
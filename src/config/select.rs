@@ -0,0 +1,346 @@
+use std::{
+    fmt::{self, Display},
+    io::{Error, ErrorKind},
+    net::{IpAddr, Ipv6Addr},
+    str::FromStr,
+};
+
+use pnet::ipnetwork::IpNetwork;
+
+/// 地址范围，对应 `[[record]]` 中 `exclude`、`include`、`prefer`、`allow`、`deny` 的每一项
+#[derive(Debug, Clone, PartialEq)]
+pub enum AddressRange {
+    /// `link-local`：链路本地地址（169.254.0.0/16、fe80::/10）
+    LinkLocal,
+    /// `ula`：IPv6唯一本地地址（fc00::/7）
+    Ula,
+    /// `private`：IPv4私有地址及运营商级NAT地址（10.0.0.0/8、172.16.0.0/12、192.168.0.0/16、100.64.0.0/10）
+    Private,
+    /// `documentation`：文档示例地址（192.0.2.0/24、198.51.100.0/24、203.0.113.0/24、2001:db8::/32）
+    Documentation,
+    /// CIDR，如 `2001:db8::/32`
+    Cidr(IpNetwork),
+}
+
+impl AddressRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self, ip) {
+            (AddressRange::LinkLocal, IpAddr::V4(ip)) => ip.is_link_local(),
+            (AddressRange::LinkLocal, IpAddr::V6(ip)) => ip.segments()[0] & 0xffc0 == 0xfe80,
+            (AddressRange::Ula, IpAddr::V6(ip)) => ip.segments()[0] & 0xfe00 == 0xfc00,
+            (AddressRange::Private, IpAddr::V4(ip)) => {
+                ip.is_private() || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64)
+            }
+            (AddressRange::Documentation, IpAddr::V4(ip)) => matches!(
+                ip.octets(),
+                [192, 0, 2, _] | [198, 51, 100, _] | [203, 0, 113, _]
+            ),
+            (AddressRange::Documentation, IpAddr::V6(ip)) => {
+                ip.segments()[0] == 0x2001 && ip.segments()[1] == 0x0db8
+            }
+            (AddressRange::Cidr(network), ip) => network.contains(ip),
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for AddressRange {
+    type Err = Error;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        match range {
+            "link-local" => Ok(AddressRange::LinkLocal),
+            "ula" => Ok(AddressRange::Ula),
+            "private" => Ok(AddressRange::Private),
+            "documentation" => Ok(AddressRange::Documentation),
+            _ => range
+                .parse::<IpNetwork>()
+                .map(AddressRange::Cidr)
+                .map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Address range \"{}\" is invalid.", range),
+                    )
+                }),
+        }
+    }
+}
+
+impl Display for AddressRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressRange::LinkLocal => write!(f, "link-local"),
+            AddressRange::Ula => write!(f, "ula"),
+            AddressRange::Private => write!(f, "private"),
+            AddressRange::Documentation => write!(f, "documentation"),
+            AddressRange::Cidr(network) => write!(f, "{}", network),
+        }
+    }
+}
+
+/// 地址作用域，对应 `[[record]]` 中的 `scope`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressScope {
+    /// `any`：不限制，全局地址优先
+    Any,
+    /// `global`：仅使用全局地址
+    Global,
+}
+
+impl FromStr for AddressScope {
+    type Err = Error;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        match scope {
+            "any" => Ok(AddressScope::Any),
+            "global" => Ok(AddressScope::Global),
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Address scope \"{}\" is invalid.", scope),
+            )),
+        }
+    }
+}
+
+/// 同一网卡上有多个地址时的选择策略，对应 `[[record]]` 中的 `address-policy`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressPolicy {
+    /// `stable`：优先使用稳定地址
+    Stable,
+    /// `temporary`：优先使用RFC 4941临时地址，有多个时使用最新的
+    Temporary,
+    /// `longest-lifetime`：优先使用首选生存期最长的地址
+    LongestLifetime,
+}
+
+impl FromStr for AddressPolicy {
+    type Err = Error;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "stable" => Ok(AddressPolicy::Stable),
+            "temporary" => Ok(AddressPolicy::Temporary),
+            "longest-lifetime" => Ok(AddressPolicy::LongestLifetime),
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Address policy \"{}\" is invalid.", policy),
+            )),
+        }
+    }
+}
+
+/// 前缀委派模式下的接口标识，对应 `[[record]]` 中的 `suffix`
+///
+/// 可以是 `::1234` 形式的IPv6地址，或 `eui64:<MAC地址>`（由MAC地址生成修改后的EUI-64标识）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AddressSuffix(pub Ipv6Addr);
+
+impl AddressSuffix {
+    /// 将地址的前 `prefix_len` 位与接口标识组合
+    pub fn apply(&self, prefix: Ipv6Addr, prefix_len: u8) -> Ipv6Addr {
+        let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+        Ipv6Addr::from((u128::from(prefix) & mask) | (u128::from(self.0) & !mask))
+    }
+}
+
+impl FromStr for AddressSuffix {
+    type Err = Error;
+
+    fn from_str(suffix: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Address suffix \"{}\" is invalid.", suffix),
+            )
+        };
+
+        match suffix.strip_prefix("eui64:") {
+            Some(mac) => {
+                let octets = mac
+                    .split([':', '-'])
+                    .map(|octet| match octet.len() {
+                        1 | 2 if octet.bytes().all(|c| c.is_ascii_hexdigit()) => {
+                            u8::from_str_radix(octet, 16).ok()
+                        }
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or_else(invalid)?;
+                if octets.len() != 6 {
+                    return Err(invalid());
+                }
+                // RFC 4291 附录A：在MAC地址中间插入 FF:FE，并翻转U/L位
+                let mut id = [0u8; 16];
+                id[8..11].copy_from_slice(&octets[0..3]);
+                id[11] = 0xff;
+                id[12] = 0xfe;
+                id[13..16].copy_from_slice(&octets[3..6]);
+                id[8] ^= 0x02;
+                Ok(AddressSuffix(Ipv6Addr::from(id)))
+            }
+            None => suffix
+                .parse::<Ipv6Addr>()
+                .map(AddressSuffix)
+                .map_err(|_| invalid()),
+        }
+    }
+}
+
+/// 是否为全局地址：排除未指定、回环、链路本地、私有、唯一本地、组播等地址
+pub fn is_global(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            !(v4.is_unspecified()
+                || v4.is_loopback()
+                || v4.is_multicast()
+                || v4.is_broadcast()
+                || AddressRange::LinkLocal.contains(ip)
+                || AddressRange::Private.contains(ip))
+        }
+        IpAddr::V6(v6) => {
+            !(v6.is_unspecified()
+                || v6.is_loopback()
+                || v6.is_multicast()
+                || AddressRange::LinkLocal.contains(ip)
+                || AddressRange::Ula.contains(ip)
+                // 已弃用的站点本地地址 fec0::/10
+                || v6.segments()[0] & 0xffc0 == 0xfec0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eui64_suffix() {
+        // RFC 4291 附录A的示例：MAC 00:11:22:33:44:55 生成 0211:22ff:fe33:4455
+        let expected: Ipv6Addr = "::211:22ff:fe33:4455".parse().unwrap();
+        for suffix in ["eui64:00:11:22:33:44:55", "eui64:00-11-22-33-44-55"] {
+            assert_eq!(suffix.parse::<AddressSuffix>().unwrap().0, expected);
+        }
+        // 已置位的U/L位被翻转为0
+        assert_eq!(
+            "eui64:02:00:5e:10:00:01"
+                .parse::<AddressSuffix>()
+                .unwrap()
+                .0,
+            "::0:5eff:fe10:1".parse::<Ipv6Addr>().unwrap()
+        );
+
+        for suffix in [
+            "eui64:",
+            "eui64:00:11:22:33:44",
+            "eui64:00:11:22:33:44:55:66",
+            "eui64:00:11:22:33:44:5g",
+            "eui64:00:11:22:33:44:555",
+            "eui64:+0:11:22:33:44:55",
+            "eui64:00::22:33:44:55",
+            "00:11:22:33:44:55",
+            "1234",
+        ] {
+            assert!(suffix.parse::<AddressSuffix>().is_err(), "{suffix}");
+        }
+    }
+
+    #[test]
+    fn apply_suffix() {
+        let eui64: AddressSuffix = "eui64:00:11:22:33:44:55".parse().unwrap();
+        let suffix: AddressSuffix = "::1234".parse().unwrap();
+        let delegated: Ipv6Addr = "2001:db8:abcd:12ff:aaaa:bbbb:cccc:dddd".parse().unwrap();
+
+        // /64 前缀：保留前64位
+        assert_eq!(
+            eui64.apply(delegated, 64),
+            "2001:db8:abcd:12ff:211:22ff:fe33:4455"
+                .parse::<Ipv6Addr>()
+                .unwrap()
+        );
+        assert_eq!(
+            suffix.apply(delegated, 64),
+            "2001:db8:abcd:12ff::1234".parse::<Ipv6Addr>().unwrap()
+        );
+
+        // /56 前缀：子网号来自接口标识
+        let subnet: AddressSuffix = "0:0:0:34::1".parse().unwrap();
+        assert_eq!(
+            subnet.apply(delegated, 56),
+            "2001:db8:abcd:1234::1".parse::<Ipv6Addr>().unwrap()
+        );
+        assert_eq!(
+            eui64.apply(delegated, 56),
+            "2001:db8:abcd:1200:211:22ff:fe33:4455"
+                .parse::<Ipv6Addr>()
+                .unwrap()
+        );
+
+        assert_eq!(suffix.apply(delegated, 128), delegated);
+        assert_eq!(suffix.apply(delegated, 0), suffix.0);
+    }
+
+    #[test]
+    fn parse_address_range() {
+        assert_eq!(
+            "link-local".parse::<AddressRange>().unwrap(),
+            AddressRange::LinkLocal
+        );
+        assert_eq!("ula".parse::<AddressRange>().unwrap(), AddressRange::Ula);
+        for range in [
+            "2001:db8:abcd:1200::/56",
+            "2001:db8:abcd:12ff::/64",
+            "10.0.0.0/8",
+        ] {
+            let parsed = range.parse::<AddressRange>().unwrap();
+            assert_eq!(parsed.to_string(), range);
+        }
+        for range in ["", "global", "2001:db8::/129", "10.0.0.0/33", "10.0.0"] {
+            assert!(range.parse::<AddressRange>().is_err(), "{range}");
+        }
+    }
+
+    #[test]
+    fn address_range_contains() {
+        let contains = |range: &str, ip: &str| {
+            range
+                .parse::<AddressRange>()
+                .unwrap()
+                .contains(ip.parse().unwrap())
+        };
+
+        let cases = [
+            // /56 委派前缀
+            ("2001:db8:abcd:1200::/56", "2001:db8:abcd:1200::1", true),
+            (
+                "2001:db8:abcd:1200::/56",
+                "2001:db8:abcd:12ff:ffff::1",
+                true,
+            ),
+            ("2001:db8:abcd:1200::/56", "2001:db8:abcd:1300::1", false),
+            // /64 子网
+            ("2001:db8:abcd:12ff::/64", "2001:db8:abcd:12ff::1", true),
+            ("2001:db8:abcd:12ff::/64", "2001:db8:abcd:12fe::1", false),
+            // 地址族不同时不包含
+            ("2001:db8::/32", "192.0.2.1", false),
+            ("0.0.0.0/0", "::1", false),
+            ("link-local", "169.254.1.1", true),
+            ("link-local", "fe80::1", true),
+            ("link-local", "fec0::1", false),
+            ("ula", "fd00::1", true),
+            ("ula", "fe00::1", false),
+            ("ula", "10.0.0.1", false),
+            ("private", "172.31.255.255", true),
+            ("private", "172.32.0.1", false),
+            ("private", "100.64.0.1", true),
+            ("private", "100.127.255.255", true),
+            ("private", "100.128.0.1", false),
+            ("private", "fd00::1", false),
+            ("documentation", "203.0.113.9", true),
+            ("documentation", "2001:db8:ffff::1", true),
+            ("documentation", "2001:db9::1", false),
+        ];
+        for (range, ip, expected) in cases {
+            assert_eq!(contains(range, ip), expected, "{range} {ip}");
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    fmt::{self, Display},
+    io::{Error, ErrorKind},
+    str::FromStr,
+};
+
+use pnet::util::MacAddr;
+use regex::Regex;
+
+/// 网卡的选择方式
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NicSelector {
+    /// 网卡名称
+    Name(String),
+    /// MAC地址
+    Mac(String),
+    /// 名称匹配的网卡，通配符（`*`、`?`）或以 `re:` 开头的正则表达式
+    Pattern(String),
+    /// 默认路由所在的网卡，IPv4与IPv6分别使用各自默认路由的网卡
+    DefaultRoute,
+}
+
+impl NicSelector {
+    /// 检查MAC地址或名称匹配规则是否合法
+    pub fn check(&self) -> Result<(), Error> {
+        match self {
+            NicSelector::Mac(mac) => mac.parse::<MacAddr>().map(|_| ()).map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("NIC MAC address \"{}\" is invalid.", mac),
+                )
+            }),
+            NicSelector::Pattern(pattern) => nic_pattern_regex(pattern).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Display for NicSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NicSelector::Name(name) => write!(f, "nic:{}", name),
+            NicSelector::Mac(mac) => write!(f, "nic-mac:{}", mac),
+            NicSelector::Pattern(pattern) => write!(f, "nic-pattern:{}", pattern),
+            NicSelector::DefaultRoute => write!(f, "nic-route"),
+        }
+    }
+}
+
+/// 将网卡名称匹配规则转换为正则表达式
+///
+/// 以 `re:` 开头时其后为正则表达式，否则为通配符：`*` 匹配任意个字符，`?` 匹配一个字符
+pub fn nic_pattern_regex(pattern: &str) -> Result<Regex, Error> {
+    let expression = match pattern.strip_prefix("re:") {
+        Some(expression) => expression.to_string(),
+        None => format!(
+            "^{}$",
+            regex::escape(pattern)
+                .replace("\\*", ".*")
+                .replace("\\?", ".")
+        ),
+    };
+    Regex::new(&expression).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("NIC pattern \"{}\" is invalid.", pattern),
+        )
+    })
+}
+
+/// 记录的IP来源，对应 `[[record]]` 中 `sources` 的每一项
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SourceSpec {
+    /// `nic:<网卡名称>`、`nic-mac:<MAC地址>`、`nic-pattern:<匹配规则>`、`nic-route`：网卡上的地址
+    Nic(NicSelector),
+    /// `socket`：向外连接的UDP Socket的本地地址
+    Socket,
+    /// `route`：主路由表中默认路由的出口地址
+    Route,
+    /// `http`：全部HTTP服务的一致结果；`http:<名称>`：指定的HTTP服务
+    Http(Option<String>),
+    /// `stun`：STUN服务器
+    Stun,
+    /// `dns`：DNS查询
+    Dns,
+    /// `upnp`：网关（UPnP IGD / PCP / NAT-PMP）
+    Upnp,
+    /// `command:<名称>`：外部命令的输出
+    Command(String),
+    /// `file:<名称>`：由其他程序写入的文件
+    File(String),
+}
+
+impl FromStr for SourceSpec {
+    type Err = Error;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (kind, argument) = match spec.split_once(':') {
+            Some((kind, argument)) => (kind, Some(argument)),
+            None => (spec, None),
+        };
+
+        let source = match (kind, argument) {
+            ("nic", Some(name)) if !name.is_empty() => {
+                SourceSpec::Nic(NicSelector::Name(name.to_string()))
+            }
+            ("nic-mac", Some(mac)) if !mac.is_empty() => {
+                SourceSpec::Nic(NicSelector::Mac(mac.to_string()))
+            }
+            ("nic-pattern", Some(pattern)) if !pattern.is_empty() => {
+                SourceSpec::Nic(NicSelector::Pattern(pattern.to_string()))
+            }
+            ("nic-route", None) => SourceSpec::Nic(NicSelector::DefaultRoute),
+            ("socket", None) => SourceSpec::Socket,
+            ("route", None) => SourceSpec::Route,
+            ("http", None) => SourceSpec::Http(None),
+            ("http", Some(name)) if !name.is_empty() => SourceSpec::Http(Some(name.to_string())),
+            ("stun", None) => SourceSpec::Stun,
+            ("dns", None) => SourceSpec::Dns,
+            ("upnp", None) => SourceSpec::Upnp,
+            ("command", Some(name)) if !name.is_empty() => SourceSpec::Command(name.to_string()),
+            ("file", Some(name)) if !name.is_empty() => SourceSpec::File(name.to_string()),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("IP source \"{}\" is invalid.", spec),
+                ))
+            }
+        };
+        if let SourceSpec::Nic(selector) = &source {
+            selector.check()?;
+        }
+        Ok(source)
+    }
+}
+
+impl Display for SourceSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceSpec::Nic(selector) => write!(f, "{}", selector),
+            SourceSpec::Socket => write!(f, "socket"),
+            SourceSpec::Route => write!(f, "route"),
+            SourceSpec::Http(None) => write!(f, "http"),
+            SourceSpec::Http(Some(name)) => write!(f, "http:{}", name),
+            SourceSpec::Stun => write!(f, "stun"),
+            SourceSpec::Dns => write!(f, "dns"),
+            SourceSpec::Upnp => write!(f, "upnp"),
+            SourceSpec::Command(name) => write!(f, "command:{}", name),
+            SourceSpec::File(name) => write!(f, "file:{}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_pattern() {
+        let cases = [
+            ("eth*", "eth0", true),
+            ("eth*", "eth", true),
+            ("eth*", "veth0", false),
+            ("eth?", "eth0", true),
+            ("eth?", "eth10", false),
+            ("*", "wlan0", true),
+            // 元字符按字面匹配
+            ("eth0.100", "eth0.100", true),
+            ("eth0.100", "eth0x100", false),
+            ("eth0.*", "eth0.100", true),
+            ("eth0.*", "eth0100", false),
+            ("br+lan", "br+lan", true),
+            ("br+lan", "brrlan", false),
+            ("wg[0]", "wg[0]", true),
+            ("wg[0]", "wg0", false),
+            ("ppp(0)|eth", "ppp(0)|eth", true),
+            ("ppp(0)|eth", "eth", false),
+            ("ppp^$", "ppp^$", true),
+            ("ppp\\1", "ppp\\1", true),
+        ];
+        for (pattern, name, expected) in cases {
+            let regex = nic_pattern_regex(pattern).unwrap();
+            assert_eq!(regex.is_match(name), expected, "{pattern} {name}");
+        }
+    }
+
+    #[test]
+    fn regex_pattern() {
+        let regex = nic_pattern_regex("re:^(eth|enp)\\d+$").unwrap();
+        assert!(regex.is_match("eth0"));
+        assert!(regex.is_match("enp3"));
+        assert!(!regex.is_match("enp3s0"));
+        // 正则表达式不自动加锚点
+        assert!(nic_pattern_regex("re:eth").unwrap().is_match("veth0"));
+
+        for pattern in ["re:(", "re:eth[", "re:*"] {
+            let err = nic_pattern_regex(pattern).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn parse_source_spec() {
+        let cases = [
+            (
+                "nic:eth0",
+                SourceSpec::Nic(NicSelector::Name("eth0".to_string())),
+            ),
+            (
+                "nic:eth0.100",
+                SourceSpec::Nic(NicSelector::Name("eth0.100".to_string())),
+            ),
+            (
+                "nic-mac:00:11:22:33:44:55",
+                SourceSpec::Nic(NicSelector::Mac("00:11:22:33:44:55".to_string())),
+            ),
+            (
+                "nic-pattern:eth*",
+                SourceSpec::Nic(NicSelector::Pattern("eth*".to_string())),
+            ),
+            (
+                "nic-pattern:re:^eth\\d$",
+                SourceSpec::Nic(NicSelector::Pattern("re:^eth\\d$".to_string())),
+            ),
+            ("nic-route", SourceSpec::Nic(NicSelector::DefaultRoute)),
+            ("socket", SourceSpec::Socket),
+            ("route", SourceSpec::Route),
+            ("http", SourceSpec::Http(None)),
+            ("http:ipip", SourceSpec::Http(Some("ipip".to_string()))),
+            ("stun", SourceSpec::Stun),
+            ("dns", SourceSpec::Dns),
+            ("upnp", SourceSpec::Upnp),
+            ("command:wan-ip", SourceSpec::Command("wan-ip".to_string())),
+            ("file:modem", SourceSpec::File("modem".to_string())),
+        ];
+        for (spec, expected) in cases {
+            let source = spec.parse::<SourceSpec>().unwrap();
+            assert_eq!(source, expected, "{spec}");
+            assert_eq!(source.to_string(), spec);
+        }
+    }
+
+    #[test]
+    fn reject_invalid_spec() {
+        for spec in [
+            "",
+            "nic",
+            "nic:",
+            "nic-mac:",
+            "nic-mac:00:11:22:33:44",
+            "nic-mac:eth0",
+            "nic-pattern:",
+            "nic-pattern:re:(",
+            "nic-route:eth0",
+            "socket:eth0",
+            "route:main",
+            "http:",
+            "stun:stun.example.com",
+            "command",
+            "file:",
+            "unknown",
+            "NIC:eth0",
+        ] {
+            let err = spec.parse::<SourceSpec>().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{spec:?}");
+        }
+    }
+}
@@ -1,12 +1,18 @@
 use std::{env, io::Error};
 
-use config::{load_config::load_server_config, record::Recorder, Config};
+use command::{parse_command, run_command};
+use config::{handle::ConfigHandle, load_config::load_server_config, record::Recorder, Config};
 use lazy_static::lazy_static;
-use service::ip_check::IpCheckService;
+use service::{
+    config_watch::ConfigWatcher,
+    control::{ControlListener, ControlRequest},
+    ip_check::IpCheckService,
+};
 use tokio::select;
-use tracing::{info, trace, warn, Instrument};
+use tracing::{error, info, trace, warn, Instrument};
 use util::log_collector::log_collector_init;
 
+mod command;
 mod config;
 mod service;
 mod util;
@@ -17,22 +23,38 @@ fn exit_on_error(e: Error) -> (String, Config) {
 }
 
 lazy_static! {
-    /// 全局配置
-    pub static ref GLOBAL_CONFIG: (String, Config) = load_server_config().unwrap_or_else(exit_on_error);
+    /// 全局配置（可重新加载）
+    pub static ref GLOBAL_CONFIG: ConfigHandle = ConfigHandle::new(load_server_config().unwrap_or_else(exit_on_error));
 }
 
 pub static VERSION: &str = env!("CARGO_PKG_VERSION");
 
 fn main() {
-    let recorder = Recorder::new(GLOBAL_CONFIG.0.clone());
+    // 指定了子命令时，执行子命令后退出
+    match parse_command() {
+        Ok(Some(command)) => {
+            if let Err(e) = run_command(command) {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Ok(None) => (),
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let config = GLOBAL_CONFIG.get();
 
     // 启动并测试日志记录
     // _guards 用于保证日志记录器在程序结束前不会被回收
     let _log_guards = match log_collector_init(
-        &GLOBAL_CONFIG.1.log.log_to_file,
-        &GLOBAL_CONFIG.1.log.log_path,
-        &GLOBAL_CONFIG.1.log.record_directive,
-        &GLOBAL_CONFIG.1.log.console_directive,
+        &config.log.log_to_file,
+        &config.log.log_path,
+        &config.log.record_directive,
+        &config.log.console_directive,
     ) {
         Ok(guards) => guards,
         Err(error) => {
@@ -41,6 +63,15 @@ fn main() {
         }
     };
 
+    // 读取记录文件（需在日志初始化后，以便输出迁移、恢复等信息）
+    let recorder = match Recorder::new(GLOBAL_CONFIG.dir().to_string(), &config) {
+        Ok(recorder) => recorder,
+        Err(error) => {
+            error!("Failed to load record file: {}", error);
+            std::process::exit(1);
+        }
+    };
+
     let runtime = tokio::runtime::Builder::new_current_thread()
         .worker_threads(2)
         .enable_all()
@@ -55,6 +86,9 @@ fn main() {
         tokio::sync::broadcast::channel::<()>(1);
     // 用于等待各子任务结束的信号（所有子任务均需持有_guard_sender，_guard_receiver由主线程持有）
     let (_guard_sender, mut _guard_receiver) = tokio::sync::mpsc::channel::<()>(1);
+    // 用于手动触发检查的请求（SIGUSR1 / SIGUSR2 及控制套接字）
+    let (control_sender, control_receiver) =
+        tokio::sync::mpsc::unbounded_channel::<ControlRequest>();
 
     let service_handle;
     // 启动异步周期任务：按照间隔时间检查 IP 是否发生变化
@@ -64,10 +98,12 @@ fn main() {
         service_handle = runtime.spawn(
             async move {
                 let mut ip_check_service = IpCheckService::new(
-                    GLOBAL_CONFIG.1.check.check_interval,
-                    GLOBAL_CONFIG.1.check.enable_recheck,
-                    GLOBAL_CONFIG.1.check.recheck_interval,
+                    config.check.check_interval,
+                    config.check.recheck_interval,
+                    config.check.reconcile_on_start,
+                    config.check.reconcile_interval,
                     recorder,
+                    control_receiver,
                 );
                 ip_check_service.start(shutdown_receiver).await;
                 // 等待关闭信号
@@ -76,6 +112,34 @@ fn main() {
         );
     }
 
+    // 启动异步任务：收到SIGHUP或配置文件修改后重新加载配置
+    {
+        let shutdown_receiver = server_shutdown_sender.subscribe();
+        let _guard_sender = _guard_sender.clone();
+        runtime.spawn(
+            async move {
+                let mut config_watcher = ConfigWatcher::new();
+                config_watcher.start(shutdown_receiver).await;
+                drop(_guard_sender);
+            }
+            .instrument(tracing::info_span!("ConfigWatchTask")),
+        );
+    }
+
+    // 启动异步任务：收到SIGUSR1 / SIGUSR2信号或控制套接字中的请求后立即检查
+    {
+        let shutdown_receiver = server_shutdown_sender.subscribe();
+        let _guard_sender = _guard_sender.clone();
+        runtime.spawn(
+            async move {
+                let mut control_listener = ControlListener::new(control_sender);
+                control_listener.start(shutdown_receiver).await;
+                drop(_guard_sender);
+            }
+            .instrument(tracing::info_span!("ControlTask")),
+        );
+    }
+
     // 监听 服务状态 与 停止信号Ctrl+C
     {
         let shutdown_sender = server_shutdown_sender; // 用于向各子线程发送关闭信号
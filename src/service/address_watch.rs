@@ -0,0 +1,175 @@
+/// 监听网卡地址变化（Linux: rtnetlink 通知）
+///     收到地址或网卡状态变化的通知后立即触发检查，短时间内的多次变化合并为一次
+use std::{io::Result, time::Duration};
+
+#[cfg(target_os = "linux")]
+use tokio::io::unix::AsyncFd;
+#[cfg(target_os = "linux")]
+use tracing::{debug, trace, warn};
+
+#[cfg(target_os = "linux")]
+use crate::{
+    config::select::AddressRange,
+    util::netlink::{
+        parse_address, parse_messages, NetlinkSocket, RTMGRP_IPV4_IFADDR, RTMGRP_IPV6_IFADDR,
+        RTMGRP_LINK, RTM_DELADDR, RTM_DELLINK, RTM_NEWADDR, RTM_NEWLINK,
+    },
+};
+
+#[cfg(target_os = "linux")]
+pub struct AddressWatcher {
+    socket: AsyncFd<NetlinkSocket>,
+    debounce: Duration,
+}
+
+#[cfg(target_os = "linux")]
+impl AddressWatcher {
+    /// 订阅网卡状态与IPv4 / IPv6地址变化的通知，需在 tokio 运行时中调用
+    ///
+    /// # Arguments
+    ///     debounce: 收到通知后，等待多久没有新的通知才触发检查
+    pub fn new(debounce: Duration) -> Result<AddressWatcher> {
+        let socket = NetlinkSocket::open(RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR)?;
+        socket.set_nonblocking()?;
+        Ok(AddressWatcher {
+            socket: AsyncFd::new(socket)?,
+            debounce,
+        })
+    }
+
+    /// 等待下一次有效的地址变化
+    pub async fn changed(&mut self) {
+        loop {
+            if self.receive().await {
+                break;
+            }
+        }
+
+        // 合并短时间内的连续变化，例如网卡重新拨号时的多次地址增删
+        while tokio::time::timeout(self.debounce, self.receive())
+            .await
+            .is_ok()
+        {
+            trace!("Address change debounced");
+        }
+        debug!("Network address changed");
+    }
+
+    /// 接收一个数据报，其中包含需要触发检查的通知时返回 true
+    async fn receive(&self) -> bool {
+        loop {
+            let mut guard = match self.socket.readable().await {
+                Ok(guard) => guard,
+                Err(e) => {
+                    warn!("Failed to wait for netlink notifications: {}", e);
+                    return std::future::pending().await;
+                }
+            };
+            match guard.try_io(|socket| socket.get_ref().recv()) {
+                Ok(Ok(buffer)) => return Self::is_relevant(&buffer),
+                Ok(Err(e)) => {
+                    // 通知过多时缓冲区溢出（ENOBUFS），此时无法得知具体变化，直接触发检查
+                    warn!("Failed to receive netlink notifications: {}", e);
+                    return true;
+                }
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// 链路本地及回环地址的变化不影响解析记录，忽略
+    fn is_relevant(buffer: &[u8]) -> bool {
+        let messages = match parse_messages(buffer) {
+            Ok(messages) => messages,
+            Err(_) => return true,
+        };
+        messages.iter().any(|(message_type, _, body)| {
+            if *message_type == RTM_NEWADDR || *message_type == RTM_DELADDR {
+                match parse_address(body) {
+                    Some(address) => {
+                        trace!("Address notification: {:?}", address);
+                        !(address.address.is_loopback()
+                            || AddressRange::LinkLocal.contains(address.address))
+                    }
+                    None => false,
+                }
+            } else {
+                *message_type == RTM_NEWLINK || *message_type == RTM_DELLINK
+            }
+        })
+    }
+}
+
+/// 非Linux系统不支持监听地址变化
+#[cfg(not(target_os = "linux"))]
+pub struct AddressWatcher;
+
+#[cfg(not(target_os = "linux"))]
+impl AddressWatcher {
+    pub fn new(_debounce: Duration) -> Result<AddressWatcher> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Watching address changes is only supported on Linux",
+        ))
+    }
+
+    pub async fn changed(&mut self) {
+        std::future::pending().await
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::util::netlink::fixture::{address, attribute, message};
+
+    fn address_message(message_type: u16, family: i32, octets: &[u8]) -> Vec<u8> {
+        // IFA_ADDRESS
+        let body = address(family, 64, 2, &[attribute(1, octets)]);
+        message(message_type, 0, &body)
+    }
+
+    #[test]
+    fn relevant_notifications() {
+        let global: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let link_local: std::net::Ipv6Addr = "fe80::1".parse().unwrap();
+
+        assert!(AddressWatcher::is_relevant(&address_message(
+            RTM_NEWADDR,
+            libc::AF_INET6,
+            &global.octets()
+        )));
+        assert!(AddressWatcher::is_relevant(&address_message(
+            RTM_DELADDR,
+            libc::AF_INET,
+            &[192, 0, 2, 1]
+        )));
+        assert!(!AddressWatcher::is_relevant(&address_message(
+            RTM_NEWADDR,
+            libc::AF_INET6,
+            &link_local.octets()
+        )));
+        assert!(!AddressWatcher::is_relevant(&address_message(
+            RTM_DELADDR,
+            libc::AF_INET,
+            &[127, 0, 0, 1]
+        )));
+        assert!(AddressWatcher::is_relevant(&message(
+            RTM_NEWLINK,
+            0,
+            &[0; 16]
+        )));
+    }
+
+    #[test]
+    fn malformed_notification_triggers_check() {
+        let buffer = address_message(RTM_NEWADDR, libc::AF_INET, &[127, 0, 0, 1]);
+        assert!(AddressWatcher::is_relevant(&buffer[..buffer.len() - 1]));
+        // 无法解析地址的消息被忽略
+        assert!(!AddressWatcher::is_relevant(&message(
+            RTM_NEWADDR,
+            0,
+            &[0; 4]
+        )));
+    }
+}
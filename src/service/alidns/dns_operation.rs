@@ -3,12 +3,13 @@
 ///     Note: Using Signature Method V3
 use std::{
     collections::HashMap,
+    fmt::{self, Display},
     io::{Error, ErrorKind, Result},
 };
 
 use reqwest::{
     header::{HeaderMap, HeaderValue},
-    Client, RequestBuilder,
+    Client, RequestBuilder, StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use tracing::debug;
@@ -19,6 +20,8 @@ use super::request_auth::{generate_authorization_header, generate_hashed_request
 
 static HOST: &str = "alidns.cn-shanghai.aliyuncs.com";
 static API_VERSION: &str = "2015-01-09";
+/// DescribeDomainRecords 单页最大记录数
+static MAX_PAGE_SIZE: i64 = 500;
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct DnsRecordList {
@@ -43,43 +46,76 @@ pub struct DnsRecordListWrapper {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct DnsRecord {
     #[serde(rename = "Status")]
-    status: String,
+    pub status: String,
     #[serde(rename = "Type")]
-    record_type: String,
+    pub record_type: String,
     #[serde(rename = "Remark")]
-    remark: Option<String>,
+    pub remark: Option<String>,
     #[serde(rename = "TTL")]
-    ttl: i64,
+    pub ttl: i64,
     #[serde(rename = "RecordId")]
-    record_id: String,
+    pub record_id: String,
     #[serde(rename = "Priority")]
-    priority: Option<i64>,
+    pub priority: Option<i64>,
     #[serde(rename = "RR")]
-    rr: String,
+    pub rr: String,
     #[serde(rename = "DomainName")]
-    domain_name: String,
+    pub domain_name: String,
     #[serde(rename = "Weight")]
-    weight: i32,
+    pub weight: i32,
     #[serde(rename = "Value")]
-    value: String,
+    pub value: String,
     #[serde(rename = "Line")]
-    line: String,
+    pub line: String,
     #[serde(rename = "Locked")]
-    locked: bool,
+    pub locked: bool,
     #[serde(rename = "CreateTimestamp")]
-    create_timestamp: i64,
+    pub create_timestamp: i64,
     #[serde(rename = "UpdateTimestamp")]
-    update_timestamp: i64,
+    pub update_timestamp: i64,
 }
 
+/// 增删改解析记录的返回结果
 #[derive(Deserialize, Serialize, Debug)]
-struct OperationResult {
+pub struct OperationResult {
     #[serde(rename = "RequestId")]
-    request_id: String,
+    pub request_id: String,
     #[serde(rename = "RecordId")]
-    record_id: String,
+    pub record_id: String,
+}
+
+/// 接口调用失败时的返回结果
+#[derive(Deserialize, Serialize, Debug)]
+struct ErrorResult {
+    #[serde(rename = "RequestId")]
+    request_id: Option<String>,
+    #[serde(rename = "Code")]
+    code: String,
+    #[serde(rename = "Message")]
+    message: String,
+}
+
+/// 接口返回的错误
+#[derive(Debug)]
+struct ApiError {
+    action: &'static str,
+    code: String,
+    message: String,
+    request_id: String,
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} failed: [{}] {} (RequestId: {})",
+            self.action, self.code, self.message, self.request_id
+        )
+    }
 }
 
+impl std::error::Error for ApiError {}
+
 pub struct AliyunDnsOperate {
     access_key_id: String,
     access_key_secret: String,
@@ -89,119 +125,243 @@ pub struct AliyunDnsOperate {
 impl AliyunDnsOperate {
     pub fn new() -> AliyunDnsOperate {
         AliyunDnsOperate {
-            access_key_id: GLOBAL_CONFIG.1.auth.auth_id.clone(),
-            access_key_secret: GLOBAL_CONFIG.1.auth.auth_token.clone(),
+            access_key_id: GLOBAL_CONFIG.get().auth.auth_id.clone(),
+            access_key_secret: GLOBAL_CONFIG.get().auth.auth_token.clone(),
             client: Client::new(),
         }
     }
 
+    /// 将主机记录对应类型的解析记录修改为新的记录值
     pub async fn update_dns_record(
         &self,
-        new_ip: &String,
-        record_type: &String,
-        hostname: &String,
-    ) -> Result<()> {
-        let list = self.get_dns_record_list(hostname).await?;
-
+        new_ip: &str,
+        record_type: &str,
+        hostname: &str,
+    ) -> Result<OperationResult> {
         // 获取目标解析记录的ID
-        let record_id: String = {
-            let mut ret = None;
-            for record in list.domain_records.record {
-                if record.rr == *hostname && record.record_type == *record_type {
-                    ret = Some(record.record_id);
-                    break;
-                }
+        let list = self.get_dns_record_list(hostname).await?;
+        let record_id = match list
+            .domain_records
+            .record
+            .iter()
+            .find(|record| record.rr == hostname && record.record_type == record_type)
+        {
+            // 记录值已是新的记录值（如记录文件丢失后），无需修改
+            Some(record) if record.value == new_ip => {
+                debug!(
+                    "DNS record for {} ({}) is already {}",
+                    hostname, record_type, new_ip
+                );
+                return Ok(OperationResult {
+                    request_id: list.request_id,
+                    record_id: record.record_id.clone(),
+                });
             }
+            Some(record) => record.record_id.clone(),
             // 如果没有找到对应的解析记录，则返回错误
-            if ret.is_none() {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    format!(
-                        "No record with type {} found for hostname: {}",
-                        record_type, hostname
-                    ),
-                ));
+            None => {
+                return Err(Error::other(format!(
+                    "No record with type {} found for hostname: {}",
+                    record_type, hostname
+                )));
             }
-            ret.unwrap()
         };
 
-        // 构造请求体
-        let method = "GET";
-        let action = "UpdateDomainRecord";
+        self.update_dns_record_by_id(&record_id, new_ip, record_type, hostname)
+            .await
+    }
 
+    /// 按解析记录ID修改解析记录
+    pub async fn update_dns_record_by_id(
+        &self,
+        record_id: &str,
+        value: &str,
+        record_type: &str,
+        hostname: &str,
+    ) -> Result<OperationResult> {
         // 请求参数
         let mut query: HashMap<&str, String> = HashMap::new();
-        query.insert("RecordId", record_id);
-        query.insert("RR", hostname.clone());
+        query.insert("RecordId", record_id.to_string());
+        query.insert("RR", hostname.to_string());
         query.insert("Type", record_type.to_string());
-        query.insert("Value", new_ip.clone());
+        query.insert("Value", value.to_string());
 
-        // 请求头
-        let mut headers: HeaderMap = HeaderMap::new();
-        headers.insert("x-acs-action", HeaderValue::from_static(action));
-
-        // 生成请求并发送
-        let result = self
-            .generate_authed_request(method, &query, &headers, None)
-            .send()
-            .await;
+        let result = self.call_api("UpdateDomainRecord", &query).await;
+        Self::update_result(result, record_id)
+    }
 
-        // 解析JSON返回结果
+    /// 修改解析记录的结果，记录值未变化（DomainRecordDuplicate）视为成功
+    ///
+    /// 例如请求超时后重试时，上一次请求实际已经生效
+    fn update_result(result: Result<String>, record_id: &str) -> Result<OperationResult> {
         match result {
-            Ok(response) => {
-                let text = response.text().await.unwrap();
-                debug!("Response: {}", text);
-                Ok(())
-            }
-            Err(e) => {
-                eprintln!("Failed to update DNS record: {}", e);
-                Err(Error::new(ErrorKind::Other, e.to_string()))
-            }
+            Ok(text) => Self::parse_response(&text),
+            Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<ApiError>()) {
+                Some(error) if error.code == "DomainRecordDuplicate" => {
+                    debug!("DNS record {} already has the value", record_id);
+                    Ok(OperationResult {
+                        request_id: error.request_id.clone(),
+                        record_id: record_id.to_string(),
+                    })
+                }
+                _ => Err(e),
+            },
         }
     }
 
+    /// 新增解析记录
+    pub async fn add_dns_record(
+        &self,
+        value: &str,
+        record_type: &str,
+        hostname: &str,
+    ) -> Result<OperationResult> {
+        // 请求参数
+        let mut query: HashMap<&str, String> = HashMap::new();
+        query.insert("DomainName", GLOBAL_CONFIG.get().domain_name.clone());
+        query.insert("RR", hostname.to_string());
+        query.insert("Type", record_type.to_string());
+        query.insert("Value", value.to_string());
+
+        let text = self.call_api("AddDomainRecord", &query).await?;
+        Self::parse_response(&text)
+    }
+
+    /// 按解析记录ID删除解析记录
+    pub async fn delete_dns_record(&self, record_id: &str) -> Result<OperationResult> {
+        // 请求参数
+        let mut query: HashMap<&str, String> = HashMap::new();
+        query.insert("RecordId", record_id.to_string());
+
+        let text = self.call_api("DeleteDomainRecord", &query).await?;
+        Self::parse_response(&text)
+    }
+
+    /// 获取主机记录与记录类型完全匹配的解析记录
+    pub async fn find_dns_records(
+        &self,
+        hostname: &str,
+        record_type: &str,
+    ) -> Result<Vec<DnsRecord>> {
+        let list = self.get_dns_record_list(hostname).await?;
+        Ok(list
+            .domain_records
+            .record
+            .into_iter()
+            .filter(|record| record.rr == hostname && record.record_type == record_type)
+            .collect())
+    }
+
     /// 获取目标域名和具有类似主机记录值解析记录列表
-    pub async fn get_dns_record_list(&self, hostname: &String) -> Result<DnsRecordList> {
-        let method = "GET";
-        let action = "DescribeDomainRecords";
-        let domain = GLOBAL_CONFIG.1.domain_name.clone();
+    pub async fn get_dns_record_list(&self, hostname: &str) -> Result<DnsRecordList> {
+        self.describe_domain_records(Some(hostname), 1, MAX_PAGE_SIZE)
+            .await
+    }
+
+    /// 获取目标域名下的全部解析记录（自动翻页）
+    pub async fn get_all_dns_records(&self) -> Result<Vec<DnsRecord>> {
+        let mut records = Vec::new();
+        let mut page_number = 1;
+
+        loop {
+            let list = self
+                .describe_domain_records(None, page_number, MAX_PAGE_SIZE)
+                .await?;
+            let fetched = list.domain_records.record.len();
+            records.extend(list.domain_records.record);
+
+            // 已取得全部记录，或当前页为空时停止翻页
+            if fetched == 0 || records.len() as i64 >= list.total_count {
+                break;
+            }
+            page_number += 1;
+        }
+
+        Ok(records)
+    }
 
+    /// 调用 DescribeDomainRecords 接口，按页获取解析记录
+    async fn describe_domain_records(
+        &self,
+        rr_keyword: Option<&str>,
+        page_number: i64,
+        page_size: i64,
+    ) -> Result<DnsRecordList> {
         // 请求参数
         let mut query: HashMap<&str, String> = HashMap::new();
-        query.insert("DomainName", domain);
-        query.insert("RRKeyWord", hostname.clone());
+        query.insert("DomainName", GLOBAL_CONFIG.get().domain_name.clone());
+        if let Some(rr_keyword) = rr_keyword {
+            query.insert("RRKeyWord", rr_keyword.to_string());
+        }
+        query.insert("PageNumber", page_number.to_string());
+        query.insert("PageSize", page_size.to_string());
+
+        let text = self.call_api("DescribeDomainRecords", &query).await?;
+        Self::parse_response(&text)
+    }
 
+    /// 发送已签名的请求并返回响应内容，接口返回错误时转换为 Error
+    async fn call_api(
+        &self,
+        action: &'static str,
+        query: &HashMap<&str, String>,
+    ) -> Result<String> {
         // 请求头
         let mut headers: HeaderMap = HeaderMap::new();
         headers.insert("x-acs-action", HeaderValue::from_static(action));
 
         // 生成请求并发送
-        let result = self
-            .generate_authed_request(method, &query, &headers, None)
+        let response = self
+            .generate_authed_request("GET", query, &headers, None)
             .send()
-            .await;
-
-        // 解析JSON返回结果
-        match result {
-            Ok(response) => {
-                let text = response.text().await.unwrap();
-                debug!("Response: {}", text);
-                let dns_record_list: DnsRecordList = match serde_json::from_str(&text) {
-                    Ok(list) => list,
-                    Err(e) => {
-                        eprintln!("Something wrong happened when parsing the response: {}", e);
-                        return Err(Error::new(ErrorKind::Other, e.to_string()));
-                    }
+            .await
+            .map_err(|e| {
+                // 超时与连接失败通常是暂时的，以 ErrorKind 区分以便调用方立即重试
+                let kind = if e.is_timeout() {
+                    ErrorKind::TimedOut
+                } else if e.is_connect() {
+                    ErrorKind::ConnectionRefused
+                } else {
+                    ErrorKind::Other
                 };
-                Ok(dns_record_list)
-            }
-            Err(e) => {
-                eprintln!("Failed to get DNS record list: {}", e);
-                Err(Error::new(ErrorKind::Other, e.to_string()))
-            }
+                Error::new(kind, format!("Failed to call {}: {}", action, e))
+            })?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| Error::other(format!("Failed to read {} response: {}", action, e)))?;
+        debug!("Response: {}", text);
+
+        if status.is_success() {
+            return Ok(text);
+        }
+        Err(Self::api_error(action, status, &text))
+    }
+
+    /// 将接口返回的错误转换为 Error，可解析的错误信息以 `ApiError` 保存
+    fn api_error(action: &'static str, status: StatusCode, text: &str) -> Error {
+        match serde_json::from_str::<ErrorResult>(text) {
+            Ok(error) => Error::other(ApiError {
+                action,
+                code: error.code,
+                message: error.message,
+                request_id: error.request_id.unwrap_or_default(),
+            }),
+            Err(_) => Error::other(format!("{} failed with HTTP status {}", action, status)),
         }
     }
 
+    /// 解析JSON返回结果
+    fn parse_response<T: for<'de> Deserialize<'de>>(text: &str) -> Result<T> {
+        serde_json::from_str(text).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Something wrong happened when parsing the response: {}", e),
+            )
+        })
+    }
+
     /// 加入必要的请求头
     fn add_indispensable_headers(headers: &mut HeaderMap, payload: Option<&String>) {
         headers.insert("x-acs-version", HeaderValue::from_static(API_VERSION));
@@ -213,7 +373,7 @@ impl AliyunDnsOperate {
             "x-acs-date",
             // 格式：yyyy-MM-ddTHH:mm:ssZ
             HeaderValue::from_str(
-                &format!("{}", chrono::Utc::now().format("%G-%m-%dT%H:%M:%SZ")).as_str(),
+                format!("{}", chrono::Utc::now().format("%G-%m-%dT%H:%M:%SZ")).as_str(),
             )
             .unwrap(),
         );
@@ -241,7 +401,7 @@ impl AliyunDnsOperate {
             &self.access_key_secret,
             method,
             "/",
-            &query,
+            query,
             &headers,
             payload,
         );
@@ -253,12 +413,12 @@ impl AliyunDnsOperate {
         match method {
             "GET" => self
                 .client
-                .get(&format!("https://{}", HOST))
+                .get(format!("https://{}", HOST))
                 .query(query)
                 .headers(headers),
             "POST" => self
                 .client
-                .post(&format!("https://{}", HOST))
+                .post(format!("https://{}", HOST))
                 .query(query)
                 .headers(headers)
                 .body(payload.unwrap().clone()),
@@ -266,3 +426,49 @@ impl AliyunDnsOperate {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::retry_queue::is_transient;
+
+    static DUPLICATE: &str = r#"{"RequestId":"6E5B4C2A","Code":"DomainRecordDuplicate","Message":"The DNS record already exists.","Recommend":""}"#;
+
+    #[test]
+    fn duplicate_after_timeout_is_success() {
+        // 第一次请求超时但实际已生效，立即重试返回 DomainRecordDuplicate
+        let timeout = Error::new(ErrorKind::TimedOut, "Failed to call UpdateDomainRecord");
+        assert!(is_transient(&timeout));
+        let error =
+            AliyunDnsOperate::api_error("UpdateDomainRecord", StatusCode::BAD_REQUEST, DUPLICATE);
+        assert!(!is_transient(&error));
+
+        let result = AliyunDnsOperate::update_result(Err(error), "1001").unwrap();
+        assert_eq!(result.record_id, "1001");
+        assert_eq!(result.request_id, "6E5B4C2A");
+    }
+
+    #[test]
+    fn other_api_errors_fail() {
+        let text = r#"{"RequestId":"7A","Code":"InvalidAccessKeyId.NotFound","Message":"Specified access key is not found."}"#;
+        let error = AliyunDnsOperate::api_error("UpdateDomainRecord", StatusCode::NOT_FOUND, text);
+        let error = AliyunDnsOperate::update_result(Err(error), "1001").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("[InvalidAccessKeyId.NotFound] Specified access key is not found."));
+
+        let error = AliyunDnsOperate::api_error(
+            "UpdateDomainRecord",
+            StatusCode::BAD_GATEWAY,
+            "<html></html>",
+        );
+        assert!(AliyunDnsOperate::update_result(Err(error), "1001").is_err());
+    }
+
+    #[test]
+    fn update_response() {
+        let text = r#"{"RequestId":"9C","RecordId":"1001"}"#;
+        let result = AliyunDnsOperate::update_result(Ok(text.to_string()), "1001").unwrap();
+        assert_eq!(result.request_id, "9C");
+    }
+}
@@ -0,0 +1,300 @@
+/// 地址变化的确认策略
+///     新地址需连续检测到若干次、并保持不变一段时间后才更新解析记录，避免DHCP抖动、PPPoE重新拨号等引起频繁更新
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, info};
+
+use crate::{config::Config, GLOBAL_CONFIG};
+
+/// 尚未确认的地址变化
+struct PendingChange {
+    ip: String,
+    count: u32,
+    first_seen: Instant,
+}
+
+pub struct ChangeConfirmer {
+    /// 各记录的确认策略，键为 (记录类型, 主机记录)
+    requirements: HashMap<(String, String), (u32, Duration)>,
+    pending: HashMap<(String, String), PendingChange>,
+}
+
+impl ChangeConfirmer {
+    pub fn new(config: &Config) -> ChangeConfirmer {
+        ChangeConfirmer {
+            requirements: Self::requirements(config),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// 按新的配置更新确认策略，仍在配置中的记录保留未确认的变化，不再配置的记录放弃
+    pub fn reload(&mut self, config: &Config) {
+        self.requirements = Self::requirements(config);
+        let requirements = &self.requirements;
+        self.pending.retain(|key, _| requirements.contains_key(key));
+    }
+
+    /// 各记录的确认策略
+    fn requirements(config: &Config) -> HashMap<(String, String), (u32, Duration)> {
+        config
+            .records
+            .iter()
+            .map(|record| {
+                let (count, time) = config.record_confirmation(record);
+                (
+                    (record.record_type.clone(), record.hostname.clone()),
+                    (count, Duration::from_secs(time)),
+                )
+            })
+            .collect()
+    }
+
+    /// 从地址发生变化的记录中选出已确认的记录
+    ///
+    /// 本轮检测到的地址与上次记录相同的记录将放弃未确认的变化，新地址与上一轮不同时重新开始计数
+    ///
+    /// # Arguments
+    ///     detected: 本轮检测到的全部地址，每项为 (记录类型, 主机记录, IP)
+    ///     changed: 其中地址与上次记录不同的记录
+    pub fn confirm(
+        &mut self,
+        detected: &[(String, String, String)],
+        changed: &[(String, String, String)],
+    ) -> Vec<(String, String, String)> {
+        // 地址已恢复的记录
+        self.pending.retain(|(record_type, hostname), pending| {
+            let restored = detected
+                .iter()
+                .any(|(t, h, _)| t == record_type && h == hostname)
+                && !changed
+                    .iter()
+                    .any(|(t, h, _)| t == record_type && h == hostname);
+            if restored {
+                info!(
+                    "Address of {}.{} ({}) went back before {} was confirmed",
+                    hostname,
+                    GLOBAL_CONFIG.get().domain_name,
+                    record_type,
+                    pending.ip
+                );
+            }
+            !restored
+        });
+
+        let now = Instant::now();
+        let mut confirmed = Vec::new();
+        for (record_type, hostname, ip) in changed {
+            let key = (record_type.clone(), hostname.clone());
+            let (required_count, required_time) = self
+                .requirements
+                .get(&key)
+                .copied()
+                .unwrap_or((1, Duration::ZERO));
+
+            let pending = self
+                .pending
+                .entry(key.clone())
+                .and_modify(|pending| {
+                    if pending.ip == *ip {
+                        pending.count += 1;
+                    } else {
+                        *pending = PendingChange {
+                            ip: ip.clone(),
+                            count: 1,
+                            first_seen: now,
+                        };
+                    }
+                })
+                .or_insert_with(|| PendingChange {
+                    ip: ip.clone(),
+                    count: 1,
+                    first_seen: now,
+                });
+
+            let stable_for = now.duration_since(pending.first_seen);
+            if pending.count >= required_count && stable_for >= required_time {
+                self.pending.remove(&key);
+                confirmed.push((record_type.clone(), hostname.clone(), ip.clone()));
+            } else {
+                debug!(
+                    "Address of {}.{} ({}) changed to {}, waiting for confirmation: seen {}/{} times, stable for {}/{} seconds",
+                    hostname,
+                    GLOBAL_CONFIG.get().domain_name,
+                    record_type,
+                    ip,
+                    pending.count,
+                    required_count,
+                    stable_for.as_secs(),
+                    required_time.as_secs()
+                );
+            }
+        }
+        confirmed
+    }
+
+    /// 放弃全部未确认的变化
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// 是否有尚未确认的地址变化
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(hostname: &str, ip: &str) -> (String, String, String) {
+        (String::from("A"), hostname.to_string(), ip.to_string())
+    }
+
+    fn confirmer(records: &str) -> ChangeConfirmer {
+        ChangeConfirmer::new(&Config::for_test(records))
+    }
+
+    /// 一轮检测：检测到的地址与上次发布的地址不同时视为变化
+    fn observe(
+        confirmer: &mut ChangeConfirmer,
+        published: &str,
+        hostname: &str,
+        ip: &str,
+    ) -> Vec<(String, String, String)> {
+        let detected = vec![target(hostname, ip)];
+        let changed: Vec<_> = detected
+            .iter()
+            .filter(|(_, _, ip)| ip != published)
+            .cloned()
+            .collect();
+        confirmer.confirm(&detected, &changed)
+    }
+
+    /// 将未确认变化的首次出现时间提前
+    fn age(confirmer: &mut ChangeConfirmer, hostname: &str, seconds: u64) {
+        let pending = confirmer
+            .pending
+            .get_mut(&(String::from("A"), hostname.to_string()))
+            .unwrap();
+        pending.first_seen -= Duration::from_secs(seconds);
+    }
+
+    #[test]
+    fn confirm_immediately_by_default() {
+        let mut confirmer = confirmer("[[record]]\nrecord-type = \"A\"\nhostname = \"www\"");
+        assert_eq!(
+            observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2"),
+            vec![target("www", "192.0.2.2")]
+        );
+        assert!(!confirmer.has_pending());
+    }
+
+    #[test]
+    fn confirm_by_count() {
+        let mut confirmer =
+            confirmer("[[record]]\nrecord-type = \"A\"\nhostname = \"www\"\nconfirm-count = 3");
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").is_empty());
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").is_empty());
+        assert!(confirmer.has_pending());
+        assert_eq!(
+            observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2"),
+            vec![target("www", "192.0.2.2")]
+        );
+        assert!(!confirmer.has_pending());
+    }
+
+    #[test]
+    fn confirm_by_time() {
+        let mut confirmer =
+            confirmer("[[record]]\nrecord-type = \"A\"\nhostname = \"www\"\nconfirm-time = 60");
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").is_empty());
+        age(&mut confirmer, "www", 30);
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").is_empty());
+        age(&mut confirmer, "www", 30);
+        assert_eq!(
+            observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2"),
+            vec![target("www", "192.0.2.2")]
+        );
+    }
+
+    #[test]
+    fn confirm_by_count_and_time() {
+        let mut confirmer = confirmer(
+            "[[record]]\nrecord-type = \"A\"\nhostname = \"www\"\nconfirm-count = 2\nconfirm-time = 60",
+        );
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").is_empty());
+        // 次数已满足，时间未满足
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").is_empty());
+        age(&mut confirmer, "www", 60);
+        assert_eq!(
+            observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn reset_when_address_flips() {
+        let mut confirmer =
+            confirmer("[[record]]\nrecord-type = \"A\"\nhostname = \"www\"\nconfirm-count = 2");
+        // 变回原地址时放弃
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").is_empty());
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.1").is_empty());
+        assert!(!confirmer.has_pending());
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").is_empty());
+
+        // 变为其他地址时重新计数
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.3").is_empty());
+        assert_eq!(
+            observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.3"),
+            vec![target("www", "192.0.2.3")]
+        );
+    }
+
+    #[test]
+    fn records_are_confirmed_independently() {
+        let mut confirmer = confirmer(
+            "[[record]]\nrecord-type = \"A\"\nhostname = \"www\"\nconfirm-count = 2\n\
+             [[record]]\nrecord-type = \"A\"\nhostname = \"api\"",
+        );
+        let detected = vec![target("www", "192.0.2.2"), target("api", "192.0.2.2")];
+        assert_eq!(
+            confirmer.confirm(&detected, &detected),
+            vec![target("api", "192.0.2.2")]
+        );
+        // 未检测到的记录保留未确认的变化
+        assert!(confirmer.confirm(&[], &[]).is_empty());
+        assert_eq!(
+            confirmer.confirm(&detected[..1], &detected[..1]),
+            vec![target("www", "192.0.2.2")]
+        );
+    }
+
+    #[test]
+    fn keep_pending_after_reload() {
+        let mut confirmer = confirmer(
+            "[[record]]\nrecord-type = \"A\"\nhostname = \"www\"\nconfirm-count = 2\n\
+             [[record]]\nrecord-type = \"A\"\nhostname = \"api\"\nconfirm-count = 2",
+        );
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").is_empty());
+        assert!(observe(&mut confirmer, "192.0.2.1", "api", "192.0.2.2").is_empty());
+
+        // api 不再配置，www 的确认次数改为3
+        confirmer.reload(&Config::for_test(
+            "[[record]]\nrecord-type = \"A\"\nhostname = \"www\"\nconfirm-count = 3",
+        ));
+        assert_eq!(confirmer.pending.len(), 1);
+        assert!(confirmer
+            .pending
+            .contains_key(&(String::from("A"), String::from("www"))));
+        // 已计的次数保留，按新的要求确认
+        assert!(observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").is_empty());
+        assert_eq!(
+            observe(&mut confirmer, "192.0.2.1", "www", "192.0.2.2").len(),
+            1
+        );
+    }
+}
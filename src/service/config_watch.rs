@@ -0,0 +1,121 @@
+/// 配置文件的重新加载
+///     收到SIGHUP信号或配置文件的修改时间变化时重新读取配置，新配置不合法时继续使用当前配置
+use std::{fs, time::SystemTime};
+
+use tracing::{debug, info, warn};
+
+use crate::GLOBAL_CONFIG;
+
+pub struct ConfigWatcher {
+    /// 配置文件最近一次的修改时间
+    modified: Option<SystemTime>,
+    hangup: Hangup,
+}
+
+impl ConfigWatcher {
+    /// 需在 tokio 运行时中调用
+    pub fn new() -> ConfigWatcher {
+        ConfigWatcher {
+            modified: Self::modified_time(),
+            hangup: Hangup::new(),
+        }
+    }
+
+    pub async fn start(&mut self, mut shutdown_receiver: tokio::sync::broadcast::Receiver<()>) {
+        loop {
+            let check = &GLOBAL_CONFIG.get().check;
+            let poll_interval = tokio::time::Duration::from_secs(check.watch_config_interval);
+            tokio::select! {
+                _ = self.hangup.recv() => {
+                    info!("Received SIGHUP, reloading configuration");
+                    self.modified = Self::modified_time();
+                }
+                _ = tokio::time::sleep(poll_interval), if check.watch_config => {
+                    let modified = Self::modified_time();
+                    if modified == self.modified {
+                        continue;
+                    }
+                    self.modified = modified;
+                    info!("Config file changed, reloading configuration");
+                }
+                _ = shutdown_receiver.recv() => break,
+            }
+            Self::reload();
+        }
+        drop(shutdown_receiver);
+    }
+
+    /// 重新加载配置，失败时保留当前配置
+    fn reload() {
+        let previous = GLOBAL_CONFIG.get();
+        if let Err(e) = GLOBAL_CONFIG.reload() {
+            warn!(
+                "Failed to reload configuration, keep using the current one: {}",
+                e
+            );
+            return;
+        }
+
+        let config = GLOBAL_CONFIG.get();
+        info!(
+            "Configuration reloaded, {} records configured",
+            config.records.len()
+        );
+        if config.log != previous.log {
+            warn!("Changes to [log] take effect after restart");
+        }
+    }
+
+    /// 配置文件的修改时间，无法读取时返回 None
+    fn modified_time() -> Option<SystemTime> {
+        match fs::metadata(GLOBAL_CONFIG.file_path()).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => Some(modified),
+            Err(e) => {
+                debug!("Failed to read modification time of config file: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// SIGHUP信号
+#[cfg(unix)]
+struct Hangup(Option<tokio::signal::unix::Signal>);
+
+#[cfg(unix)]
+impl Hangup {
+    fn new() -> Hangup {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+            Ok(signal) => Hangup(Some(signal)),
+            Err(e) => {
+                warn!("Failed to listen for SIGHUP: {}", e);
+                Hangup(None)
+            }
+        }
+    }
+
+    /// 等待下一次SIGHUP信号，无法监听时永不返回
+    async fn recv(&mut self) {
+        match &mut self.0 {
+            Some(signal) => {
+                signal.recv().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// 非Unix系统没有SIGHUP信号
+#[cfg(not(unix))]
+struct Hangup;
+
+#[cfg(not(unix))]
+impl Hangup {
+    fn new() -> Hangup {
+        Hangup
+    }
+
+    async fn recv(&mut self) {
+        std::future::pending().await
+    }
+}
@@ -0,0 +1,246 @@
+/// 手动触发检查
+///     收到SIGUSR1或控制套接字中的 `check` 请求时立即检查，收到SIGUSR2或 `republish` 请求时重新发布全部记录
+use std::{
+    fmt::{self, Display},
+    io::{Error, ErrorKind, Result},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use tokio::sync::mpsc::UnboundedSender;
+#[cfg(unix)]
+use tracing::{debug, info, warn, Instrument};
+
+use crate::GLOBAL_CONFIG;
+
+/// 控制请求
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlRequest {
+    /// 立即检查
+    Check,
+    /// 立即检查，并重新发布全部记录（即使地址没有变化）
+    Republish,
+}
+
+impl FromStr for ControlRequest {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ControlRequest> {
+        match s {
+            "check" => Ok(ControlRequest::Check),
+            "republish" => Ok(ControlRequest::Republish),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown request: {}", s),
+            )),
+        }
+    }
+}
+
+impl Display for ControlRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlRequest::Check => write!(f, "check"),
+            ControlRequest::Republish => write!(f, "republish"),
+        }
+    }
+}
+
+/// 控制套接字的路径（配置目录中的 `control.sock`）
+pub fn socket_path() -> PathBuf {
+    PathBuf::from(GLOBAL_CONFIG.dir()).join("control.sock")
+}
+
+/// 监听SIGUSR1 / SIGUSR2信号及控制套接字，将请求转交检查服务
+pub struct ControlListener {
+    sender: UnboundedSender<ControlRequest>,
+}
+
+#[cfg(unix)]
+impl ControlListener {
+    pub fn new(sender: UnboundedSender<ControlRequest>) -> ControlListener {
+        ControlListener { sender }
+    }
+
+    /// 需在 tokio 运行时中调用
+    pub async fn start(&mut self, mut shutdown_receiver: tokio::sync::broadcast::Receiver<()>) {
+        use tokio::signal::unix::SignalKind;
+
+        let mut check_signal = Self::signal(SignalKind::user_defined1(), "SIGUSR1");
+        let mut republish_signal = Self::signal(SignalKind::user_defined2(), "SIGUSR2");
+        let listener = if GLOBAL_CONFIG.get().check.control_socket {
+            Self::bind()
+        } else {
+            None
+        };
+
+        loop {
+            let request = tokio::select! {
+                _ = Self::recv_signal(&mut check_signal) => {
+                    info!("Received SIGUSR1, checking now");
+                    ControlRequest::Check
+                }
+                _ = Self::recv_signal(&mut republish_signal) => {
+                    info!("Received SIGUSR2, republishing all records");
+                    ControlRequest::Republish
+                }
+                result = Self::accept(&listener) => match result {
+                    Ok(stream) => {
+                        // 在单独的任务中处理，避免缓慢的客户端阻塞信号的处理
+                        tokio::spawn(Self::serve(self.sender.clone(), stream).in_current_span());
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Failed to accept control connection: {}", e);
+                        continue;
+                    }
+                },
+                _ = shutdown_receiver.recv() => break,
+            };
+            Self::send(&self.sender, request);
+        }
+
+        if listener.is_some() {
+            let _ = std::fs::remove_file(socket_path());
+        }
+        drop(shutdown_receiver);
+    }
+
+    /// 将请求转交检查服务
+    fn send(sender: &UnboundedSender<ControlRequest>, request: ControlRequest) {
+        if sender.send(request).is_err() {
+            debug!("Check service is not running, request {} dropped", request);
+        }
+    }
+
+    /// 处理控制套接字上的一个请求：读取一行请求，回复 `ok` 或 `error: <原因>`
+    async fn serve(sender: UnboundedSender<ControlRequest>, stream: tokio::net::UnixStream) {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let mut stream = BufReader::new(stream);
+        let mut line = String::new();
+        let read = tokio::time::timeout(
+            tokio::time::Duration::from_secs(5),
+            stream.read_line(&mut line),
+        )
+        .await;
+        let reply = match read {
+            Ok(Ok(_)) => match line.trim().parse::<ControlRequest>() {
+                Ok(request) => {
+                    info!("Received {} request from control socket", request);
+                    Self::send(&sender, request);
+                    String::from("ok\n")
+                }
+                Err(e) => format!("error: {}\n", e),
+            },
+            Ok(Err(e)) => format!("error: {}\n", e),
+            Err(_) => String::from("error: Timed out waiting for request\n"),
+        };
+        if let Err(e) = stream.get_mut().write_all(reply.as_bytes()).await {
+            debug!("Failed to reply on control socket: {}", e);
+        }
+    }
+
+    /// 创建控制套接字，仅允许当前用户访问
+    fn bind() -> Option<tokio::net::UnixListener> {
+        let path = socket_path();
+        // 记录目录已加锁，残留的套接字文件来自上一次运行
+        let _ = std::fs::remove_file(&path);
+        // 以 0600 权限创建套接字文件，避免创建后再修改权限之间的空隙
+        let umask = unsafe { libc::umask(0o177) };
+        let listener = tokio::net::UnixListener::bind(&path);
+        unsafe { libc::umask(umask) };
+        match listener {
+            Ok(listener) => {
+                debug!("Listening on control socket {}", path.display());
+                Some(listener)
+            }
+            Err(e) => {
+                warn!("Failed to create control socket {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn signal(
+        kind: tokio::signal::unix::SignalKind,
+        name: &str,
+    ) -> Option<tokio::signal::unix::Signal> {
+        match tokio::signal::unix::signal(kind) {
+            Ok(signal) => Some(signal),
+            Err(e) => {
+                warn!("Failed to listen for {}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    /// 等待信号，无法监听时永不返回
+    async fn recv_signal(signal: &mut Option<tokio::signal::unix::Signal>) {
+        match signal {
+            Some(signal) => {
+                signal.recv().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    /// 等待控制套接字上的连接，未创建套接字时永不返回
+    async fn accept(listener: &Option<tokio::net::UnixListener>) -> Result<tokio::net::UnixStream> {
+        match listener {
+            Some(listener) => listener.accept().await.map(|(stream, _)| stream),
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// 非Unix系统不支持信号及控制套接字
+#[cfg(not(unix))]
+impl ControlListener {
+    pub fn new(sender: UnboundedSender<ControlRequest>) -> ControlListener {
+        ControlListener { sender }
+    }
+
+    pub async fn start(&mut self, mut shutdown_receiver: tokio::sync::broadcast::Receiver<()>) {
+        let _ = shutdown_receiver.recv().await;
+    }
+}
+
+/// 通过控制套接字向运行中的服务发送请求
+#[cfg(unix)]
+pub async fn send_request(request: ControlRequest) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let path = socket_path();
+    let mut stream = tokio::net::UnixStream::connect(&path).await.map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!(
+                "Failed to connect to the service via {}: {}",
+                path.display(),
+                e
+            ),
+        )
+    })?;
+    stream
+        .write_all(format!("{}\n", request).as_bytes())
+        .await?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).await?;
+    match reply.trim() {
+        "ok" => Ok(()),
+        reply => Err(Error::other(
+            reply.strip_prefix("error: ").unwrap_or(reply).to_string(),
+        )),
+    }
+}
+
+/// 非Unix系统不支持控制套接字
+#[cfg(not(unix))]
+pub async fn send_request(_request: ControlRequest) -> Result<()> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "Control socket is only supported on Unix",
+    ))
+}
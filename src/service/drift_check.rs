@@ -0,0 +1,191 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use tracing::{debug, info, warn};
+
+use crate::{
+    util::dns_client::{query, resolve_server, system_resolver, QueryType, RData},
+    GLOBAL_CONFIG,
+};
+
+/// 通过直接查询权威DNS服务器，检查外部实际看到的解析结果是否与预期一致
+pub struct DriftChecker {
+    domain_name: String,
+    nameservers: Vec<String>,
+    resolver: String,
+    use_tcp: bool,
+    timeout: Duration,
+    /// 每隔多少次检查进行一次
+    pub interval: u64,
+    /// 更新解析记录后等待多久再检查（单位：秒）
+    pub update_delay: u64,
+}
+
+impl DriftChecker {
+    pub fn new() -> DriftChecker {
+        let drift = &GLOBAL_CONFIG.get().drift_check;
+        DriftChecker {
+            domain_name: GLOBAL_CONFIG.get().domain_name.clone(),
+            nameservers: drift.nameservers.clone(),
+            resolver: drift.resolver.clone(),
+            use_tcp: drift.use_tcp,
+            timeout: Duration::from_secs(drift.timeout),
+            interval: drift.interval,
+            update_delay: drift.update_delay,
+        }
+    }
+
+    /// 获取域名的权威DNS服务器地址（未配置时通过NS记录查找）
+    pub async fn authoritative_servers(&self) -> Result<Vec<SocketAddr>> {
+        let names = if self.nameservers.is_empty() {
+            let resolver = if self.resolver.is_empty() {
+                system_resolver()
+            } else {
+                match resolve_server(&self.resolver).await?.first() {
+                    Some(resolver) => *resolver,
+                    None => {
+                        return Err(Error::new(
+                            ErrorKind::NotFound,
+                            format!("Failed to resolve resolver: {}", self.resolver),
+                        ))
+                    }
+                }
+            };
+
+            let response = query(
+                resolver,
+                &self.domain_name,
+                QueryType::Ns,
+                self.use_tcp,
+                self.timeout,
+            )
+            .await?;
+            response
+                .answers
+                .into_iter()
+                .filter_map(|answer| match answer {
+                    RData::Ns(name) => Some(name),
+                    _ => None,
+                })
+                .collect()
+        } else {
+            self.nameservers.clone()
+        };
+
+        if names.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("No nameserver found for {}", self.domain_name),
+            ));
+        }
+        debug!("Nameservers of {}: {:?}", self.domain_name, names);
+
+        let mut servers = Vec::new();
+        for name in names.iter() {
+            match resolve_server(name).await {
+                Ok(addrs) => servers.extend(addrs),
+                Err(e) => warn!("Failed to resolve nameserver {}: {}", name, e),
+            }
+        }
+        servers.sort();
+        servers.dedup();
+
+        Ok(servers)
+    }
+
+    /// 查询指定服务器上主机记录的解析结果
+    pub async fn query_served(
+        &self,
+        server: SocketAddr,
+        hostname: &str,
+        record_type: &str,
+    ) -> Result<Vec<IpAddr>> {
+        let query_type = QueryType::from_record_type(record_type).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported record type: {}", record_type),
+            )
+        })?;
+        let response = query(
+            server,
+            &GLOBAL_CONFIG.get().fqdn(hostname),
+            query_type,
+            self.use_tcp,
+            self.timeout,
+        )
+        .await?;
+
+        // NOERROR 与 NXDOMAIN 之外的响应码视为查询失败
+        match response.rcode {
+            0 | 3 => Ok(response.addresses()),
+            rcode => Err(Error::other(format!(
+                "{} answered {} with rcode {}",
+                server, hostname, rcode
+            ))),
+        }
+    }
+
+    /// 检查权威服务器上的解析结果是否与预期一致，targets 中每项为 (记录类型, 主机记录, 已发布的IP)
+    ///
+    /// # Return
+    ///     usize: 解析结果不一致的记录数
+    pub async fn check(&self, targets: &[(String, String, String)]) -> usize {
+        let servers = match self.authoritative_servers().await {
+            Ok(servers) if !servers.is_empty() => servers,
+            Ok(_) => {
+                warn!("Drift check skipped: no reachable nameserver");
+                return 0;
+            }
+            Err(e) => {
+                warn!("Drift check skipped: {}", e);
+                return 0;
+            }
+        };
+
+        let mut drifted_count = 0;
+        for (record_type, hostname, ip) in targets {
+            let fqdn = GLOBAL_CONFIG.get().fqdn(hostname);
+            let mut answered = false;
+            let mut drifted = false;
+
+            for server in servers.iter() {
+                match self.query_served(*server, hostname, record_type).await {
+                    Ok(served) => {
+                        answered = true;
+                        let served: Vec<String> = served.iter().map(|ip| ip.to_string()).collect();
+                        if served.len() != 1 || served[0] != *ip {
+                            drifted = true;
+                            warn!(
+                                "DNS drift detected: {} {} served by {} is {:?}, expected {}",
+                                fqdn, record_type, server, served, ip
+                            );
+                        }
+                    }
+                    Err(e) => debug!("Failed to query {} for {}: {}", server, fqdn, e),
+                }
+            }
+
+            if !answered {
+                warn!(
+                    "Unable to verify {} {}: no nameserver answered",
+                    fqdn, record_type
+                );
+            } else if drifted {
+                drifted_count += 1;
+            } else {
+                debug!(
+                    "{} {} is served as {} by all nameservers",
+                    fqdn, record_type, ip
+                );
+            }
+        }
+
+        if drifted_count == 0 {
+            info!("Drift check complete, all served records match");
+        }
+        drifted_count
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    process::Stdio,
+    time::Duration,
+};
+
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::config::CommandSource;
+
+use super::{parse_ip_list, SourceAddress};
+
+/// Get IP addresses printed by an external command
+///
+/// The command is run without a shell, with stdin closed, and killed if it does not exit
+/// within the timeout. It must exit successfully; every address found in stdout is returned.
+pub async fn get_ip_via_command(
+    command: &CommandSource,
+    timeout: Duration,
+) -> Result<Vec<SourceAddress>> {
+    let child = Command::new(&command.command)
+        .args(&command.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| {
+            Error::new(
+                ErrorKind::TimedOut,
+                format!("Command \"{}\" timed out", command.command),
+            )
+        })??;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    debug!("Command \"{}\" output: {}", command.name, stdout.trim());
+    if !output.status.success() {
+        return Err(Error::other(format!(
+            "Command \"{}\" exited with {}: {}",
+            command.command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    parse_ip_list(&stdout)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::util::dns_client::stub::block_on;
+
+    use super::*;
+
+    fn shell(script: &str) -> CommandSource {
+        CommandSource {
+            name: "test".to_string(),
+            command: "/bin/sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+        }
+    }
+
+    #[test]
+    fn parse_command_output() {
+        let command = shell("echo '# wan addresses'; echo 203.0.113.7; echo; echo 2001:db8::7/64");
+        let ips: Vec<String> = block_on(get_ip_via_command(&command, Duration::from_secs(5)))
+            .unwrap()
+            .into_iter()
+            .map(|address| address.ip.to_string())
+            .collect();
+        assert_eq!(ips, ["203.0.113.7/32", "2001:db8::7/64"]);
+    }
+
+    #[test]
+    fn command_failures() {
+        // non-zero exit status, even with an address in stdout
+        let err = block_on(get_ip_via_command(
+            &shell("echo 203.0.113.7; echo failed >&2; exit 3"),
+            Duration::from_secs(5),
+        ))
+        .unwrap_err();
+        assert!(err.to_string().contains("failed"), "{err}");
+
+        let err = block_on(get_ip_via_command(
+            &shell("echo garbage"),
+            Duration::from_secs(5),
+        ))
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let err = block_on(get_ip_via_command(
+            &shell("sleep 5"),
+            Duration::from_millis(100),
+        ))
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+        let mut missing = shell("");
+        missing.command = "/nonexistent/command".to_string();
+        assert!(block_on(get_ip_via_command(&missing, Duration::from_secs(5))).is_err());
+    }
+}
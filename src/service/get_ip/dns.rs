@@ -0,0 +1,185 @@
+use std::{net::IpAddr, time::Duration};
+
+use pnet::ipnetwork::IpNetwork;
+use tracing::debug;
+
+use crate::{
+    config::{DnsDetectQuery, IP},
+    util::dns_client::{query, resolve_server, QueryType, RData},
+};
+
+/// Get IP address by asking a resolver for a "whoami" name
+///
+/// e.g. A/AAAA `myip.opendns.com` at `resolver1.opendns.com`, or TXT `o-o.myaddr.l.google.com`
+/// at `ns1.google.com`. Queries are tried in order until both address families are found.
+pub async fn get_ip_via_dns(queries: &[DnsDetectQuery], timeout: Duration) -> Option<IP> {
+    let mut v4_addr: Option<IpAddr> = None;
+    let mut v6_addr: Option<IpAddr> = None;
+
+    for dns_query in queries.iter() {
+        if v4_addr.is_some() && v6_addr.is_some() {
+            break;
+        }
+
+        let query_type = match dns_query.query_type.as_str() {
+            "A" => QueryType::A,
+            "AAAA" => QueryType::Aaaa,
+            _ => QueryType::Txt,
+        };
+        let servers = match resolve_server(&dns_query.server).await {
+            Ok(servers) => servers,
+            Err(e) => {
+                debug!("Failed to resolve DNS server {}: {}", dns_query.server, e);
+                continue;
+            }
+        };
+
+        for server in servers {
+            // A is answered with the IPv4 address, AAAA with the IPv6 address,
+            // TXT with the address the query was sent from
+            let wanted_v4 = match query_type {
+                QueryType::A => true,
+                QueryType::Aaaa => false,
+                _ => server.is_ipv4(),
+            };
+            let slot = if wanted_v4 {
+                &mut v4_addr
+            } else {
+                &mut v6_addr
+            };
+            if slot.is_some() {
+                continue;
+            }
+
+            let response = match query(server, &dns_query.name, query_type, false, timeout).await {
+                Ok(response) => response,
+                Err(e) => {
+                    debug!("DNS query to {} failed: {}", server, e);
+                    continue;
+                }
+            };
+            let ip = response.answers.iter().find_map(|answer| match answer {
+                RData::A(ip) => Some(IpAddr::V4(*ip)),
+                RData::Aaaa(ip) => Some(IpAddr::V6(*ip)),
+                RData::Txt(text) => text.trim().parse::<IpAddr>().ok(),
+                _ => None,
+            });
+            match ip {
+                Some(ip) if ip.is_ipv4() == wanted_v4 => {
+                    debug!(
+                        "DNS server {} reported {} for {}",
+                        server, ip, dns_query.name
+                    );
+                    *slot = Some(ip);
+                }
+                _ => debug!(
+                    "DNS server {} returned no usable address for {}",
+                    server, dns_query.name
+                ),
+            }
+        }
+    }
+
+    if v4_addr.is_none() && v6_addr.is_none() {
+        None
+    } else {
+        Some(IP {
+            v4: v4_addr.map(IpNetwork::from),
+            v6: v6_addr.map(IpNetwork::from),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::util::dns_client::stub::{block_on, serve_udp, txt};
+
+    const V4: Ipv4Addr = Ipv4Addr::new(203, 0, 113, 7);
+    const V6: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 7);
+
+    /// Start a stub server answering with the records and build a query against it
+    async fn stub_query(query_type: &str, answers: Vec<(u16, Vec<u8>)>) -> DnsDetectQuery {
+        DnsDetectQuery {
+            server: serve_udp(answers).await.to_string(),
+            name: String::from("myip.example.com"),
+            query_type: String::from(query_type),
+        }
+    }
+
+    #[test]
+    fn whoami_a() {
+        let ip = block_on(async {
+            let query = stub_query("A", vec![(1, V4.octets().to_vec())]).await;
+            get_ip_via_dns(&[query], Duration::from_secs(2)).await
+        })
+        .unwrap();
+        assert_eq!(ip.v4, Some(IpNetwork::from(IpAddr::V4(V4))));
+        assert_eq!(ip.v6, None);
+    }
+
+    #[test]
+    fn whoami_aaaa() {
+        let ip = block_on(async {
+            let query = stub_query("AAAA", vec![(28, V6.octets().to_vec())]).await;
+            get_ip_via_dns(&[query], Duration::from_secs(2)).await
+        })
+        .unwrap();
+        assert_eq!(ip.v4, None);
+        assert_eq!(ip.v6, Some(IpNetwork::from(IpAddr::V6(V6))));
+    }
+
+    #[test]
+    fn whoami_txt() {
+        // The stub listens on IPv4, so the TXT answer is taken as the IPv4 address
+        let ip = block_on(async {
+            let query = stub_query("TXT", vec![(16, txt(&[" 203.0.113.7 "]))]).await;
+            get_ip_via_dns(&[query], Duration::from_secs(2)).await
+        })
+        .unwrap();
+        assert_eq!(ip.v4, Some(IpNetwork::from(IpAddr::V4(V4))));
+        assert_eq!(ip.v6, None);
+    }
+
+    #[test]
+    fn both_families_from_several_queries() {
+        let ip = block_on(async {
+            let queries = [
+                stub_query("A", vec![(1, V4.octets().to_vec())]).await,
+                stub_query("AAAA", vec![(28, V6.octets().to_vec())]).await,
+            ];
+            get_ip_via_dns(&queries, Duration::from_secs(2)).await
+        })
+        .unwrap();
+        assert_eq!(ip.v4, Some(IpNetwork::from(IpAddr::V4(V4))));
+        assert_eq!(ip.v6, Some(IpNetwork::from(IpAddr::V6(V6))));
+    }
+
+    #[test]
+    fn unusable_answers_are_ignored() {
+        let found = block_on(async {
+            let queries = [
+                // AAAA answer to an A query: wrong family
+                stub_query("A", vec![(28, V6.octets().to_vec())]).await,
+                // TXT that is not an address
+                stub_query("TXT", vec![(16, txt(&["not an address"]))]).await,
+                // no answer at all
+                stub_query("A", Vec::new()).await,
+            ];
+            get_ip_via_dns(&queries, Duration::from_secs(2)).await
+        });
+        assert!(found.is_none());
+    }
+
+    #[test]
+    fn unreachable_server_is_skipped() {
+        let query = DnsDetectQuery {
+            server: String::from("127.0.0.1:1"),
+            name: String::from("myip.example.com"),
+            query_type: String::from("A"),
+        };
+        assert!(block_on(get_ip_via_dns(&[query], Duration::from_millis(200))).is_none());
+    }
+}
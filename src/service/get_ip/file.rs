@@ -0,0 +1,107 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    time::{Duration, SystemTime},
+};
+
+use tracing::debug;
+
+use crate::config::FileSource;
+
+use super::{parse_ip_list, SourceAddress};
+
+/// Files larger than this are not address files
+static MAX_FILE_SIZE: u64 = 64 * 1024;
+
+/// Get IP addresses written to a file by another tool
+///
+/// If `max_age` is not zero, a file not modified within `max_age` is treated as stale.
+pub fn get_ip_via_file(file: &FileSource, max_age: Duration) -> Result<Vec<SourceAddress>> {
+    let metadata = fs::metadata(&file.path)?;
+    if metadata.len() > MAX_FILE_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("File \"{}\" is too large", file.path),
+        ));
+    }
+    if !max_age.is_zero() {
+        let age = SystemTime::now()
+            .duration_since(metadata.modified()?)
+            .unwrap_or_default();
+        if age > max_age {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "File \"{}\" is stale, last modified {} seconds ago",
+                    file.path,
+                    age.as_secs()
+                ),
+            ));
+        }
+    }
+
+    let content = fs::read_to_string(&file.path)?;
+    debug!("File \"{}\" content: {}", file.name, content.trim());
+    parse_ip_list(&content)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::TempDir;
+
+    use super::*;
+
+    fn file_source(dir: &TempDir, content: &str) -> FileSource {
+        let path = dir.path().join("wan-ip");
+        fs::write(&path, content).unwrap();
+        FileSource {
+            name: "test".to_string(),
+            path: path.to_str().unwrap().to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_file_content() {
+        let dir = TempDir::new("file-source");
+        let file = file_source(
+            &dir,
+            "# ppp0\r\n203.0.113.7\r\n\r\n2001:db8::/56,2001:db8::7\r\n",
+        );
+        let ips: Vec<String> = get_ip_via_file(&file, Duration::ZERO)
+            .unwrap()
+            .into_iter()
+            .map(|address| address.ip.to_string())
+            .collect();
+        assert_eq!(ips, ["203.0.113.7/32", "2001:db8::/56", "2001:db8::7/128"]);
+
+        let file = file_source(&dir, "not an address\n");
+        assert_eq!(
+            get_ip_via_file(&file, Duration::ZERO).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn reject_stale_large_or_missing_file() {
+        let dir = TempDir::new("file-source");
+        let file = file_source(&dir, "203.0.113.7\n");
+        assert!(get_ip_via_file(&file, Duration::from_secs(60)).is_ok());
+        fs::File::options()
+            .write(true)
+            .open(&file.path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(120))
+            .unwrap();
+        assert!(get_ip_via_file(&file, Duration::from_secs(60)).is_err());
+        assert!(get_ip_via_file(&file, Duration::ZERO).is_ok());
+
+        let file = file_source(&dir, &"203.0.113.7\n".repeat(10000));
+        assert!(get_ip_via_file(&file, Duration::ZERO).is_err());
+
+        fs::remove_file(&file.path).unwrap();
+        assert_eq!(
+            get_ip_via_file(&file, Duration::ZERO).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+}
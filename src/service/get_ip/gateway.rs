@@ -0,0 +1,535 @@
+/// 通过网关（路由器）查询WAN口地址
+///     UPnP IGD: https://upnp.org/specs/gw/UPnP-gw-WANIPConnection-v2-Service.pdf
+///     NAT-PMP: https://www.rfc-editor.org/rfc/rfc6886
+///     PCP: https://www.rfc-editor.org/rfc/rfc6887
+use std::{
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use pnet::ipnetwork::IpNetwork;
+use rand::Rng;
+use reqwest::Url;
+use tokio::net::UdpSocket;
+use tracing::debug;
+
+use crate::{config::IP, util::route::default_routes_v4};
+
+static SSDP_ADDR: &str = "239.255.255.250:1900";
+static SSDP_SEARCH_TARGETS: [&str; 2] = [
+    "urn:schemas-upnp-org:device:InternetGatewayDevice:1",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+];
+static WAN_SERVICE_TYPES: [&str; 2] = ["WANIPConnection", "WANPPPConnection"];
+static NAT_PMP_PORT: u16 = 5351;
+/// How long to keep collecting SSDP responses after the first one
+static SSDP_COLLECT_WINDOW: Duration = Duration::from_millis(300);
+
+/// Get the WAN address from the gateway, trying UPnP IGD, NAT-PMP and PCP in order
+///
+/// # Arguments
+///     gateway: gateway address used by NAT-PMP / PCP, the default route gateway if None
+///     pcp: fall back to PCP, which creates a short-lived mapping on the gateway
+///     timeout: timeout of each request
+pub async fn get_ip_via_gateway(
+    gateway: Option<Ipv4Addr>,
+    pcp: bool,
+    timeout: Duration,
+) -> Option<IP> {
+    let ip = match get_ip_via_upnp(timeout).await {
+        Ok(ip) => Some(ip),
+        Err(e) => {
+            debug!("UPnP IGD failed: {}", e);
+            None
+        }
+    };
+
+    let ip = match ip {
+        Some(ip) => Some(ip),
+        None => match gateway.or_else(default_gateway) {
+            Some(gateway) => get_ip_via_port_mapping_protocol(gateway, pcp, timeout).await,
+            None => {
+                debug!("No gateway found for NAT-PMP / PCP");
+                None
+            }
+        },
+    };
+
+    ip.map(|ip| {
+        debug!("Gateway reported WAN address {}", ip);
+        IP {
+            v4: Some(IpNetwork::from(IpAddr::V4(ip))),
+            v6: None,
+        }
+    })
+}
+
+/// Gateway of the preferred IPv4 default route
+fn default_gateway() -> Option<Ipv4Addr> {
+    let (route, gateway) =
+        default_routes_v4()
+            .into_iter()
+            .find_map(|route| match route.gateway {
+                Some(IpAddr::V4(gateway)) => Some((route, gateway)),
+                _ => None,
+            })?;
+    debug!("Using default gateway {} via {}", gateway, route.interface);
+    Some(gateway)
+}
+
+/// Ask the gateway with NAT-PMP, which only reads the external address,
+/// falling back to PCP if enabled
+async fn get_ip_via_port_mapping_protocol(
+    gateway: Ipv4Addr,
+    pcp: bool,
+    timeout: Duration,
+) -> Option<Ipv4Addr> {
+    match get_ip_via_nat_pmp(gateway, timeout).await {
+        Ok(ip) => return Some(ip),
+        Err(e) => debug!("NAT-PMP failed: {}", e),
+    }
+    if !pcp {
+        return None;
+    }
+    match get_ip_via_pcp(gateway, timeout).await {
+        Ok(ip) => Some(ip),
+        Err(e) => {
+            debug!("PCP failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Discover the IGD via SSDP and call GetExternalIPAddress on its WAN connection service
+async fn get_ip_via_upnp(timeout: Duration) -> Result<Ipv4Addr> {
+    let locations = ssdp_search(timeout).await?;
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(Error::other)?;
+
+    let mut last_error = Error::new(ErrorKind::NotFound, "No IGD answered the SSDP search");
+    for location in locations {
+        match query_igd(&client, &location).await {
+            Ok(ip) => return Ok(ip),
+            Err(e) => {
+                debug!("IGD at {} failed: {}", location, e);
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+/// Send M-SEARCH requests and collect the LOCATION of the responses
+///
+/// Collecting stops shortly after the first response instead of waiting for the full timeout
+async fn ssdp_search(timeout: Duration) -> Result<Vec<String>> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    for search_target in SSDP_SEARCH_TARGETS.iter() {
+        let request = format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {}\r\n\r\n",
+            SSDP_ADDR, search_target
+        );
+        socket.send_to(request.as_bytes(), SSDP_ADDR).await?;
+    }
+
+    let mut locations: Vec<String> = Vec::new();
+    let mut buffer = [0u8; 2048];
+    let mut deadline = tokio::time::Instant::now() + timeout;
+    while let Ok(Ok((size, from))) =
+        tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await
+    {
+        if let Some(location) = parse_ssdp_location(&String::from_utf8_lossy(&buffer[..size])) {
+            debug!("SSDP response from {}: {}", from, location);
+            if locations.is_empty() {
+                deadline = deadline.min(tokio::time::Instant::now() + SSDP_COLLECT_WINDOW);
+            }
+            if !locations.contains(&location) {
+                locations.push(location);
+            }
+        }
+    }
+
+    Ok(locations)
+}
+
+/// Get the LOCATION header of an SSDP response
+fn parse_ssdp_location(response: &str) -> Option<String> {
+    response.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim().eq_ignore_ascii_case("location") {
+            Some(value.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Read the device description and ask the WAN connection service for the external address
+async fn query_igd(client: &reqwest::Client, location: &str) -> Result<Ipv4Addr> {
+    let description = client
+        .get(location)
+        .send()
+        .await
+        .map_err(Error::other)?
+        .text()
+        .await
+        .map_err(Error::other)?;
+
+    let (service_type, control_url) = find_wan_service(&description, location)?;
+
+    let body = format!(
+        "<?xml version=\"1.0\"?>\r\n\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:GetExternalIPAddress xmlns:u=\"{}\"/></s:Body></s:Envelope>",
+        service_type
+    );
+    let response = client
+        .post(control_url)
+        .header("Content-Type", "text/xml; charset=\"utf-8\"")
+        .header(
+            "SOAPAction",
+            format!("\"{}#GetExternalIPAddress\"", service_type),
+        )
+        .body(body)
+        .send()
+        .await
+        .map_err(Error::other)?
+        .text()
+        .await
+        .map_err(Error::other)?;
+
+    parse_external_ip(&response)
+}
+
+/// Find the WANIPConnection / WANPPPConnection service in the device description
+///
+/// # Return
+///     Result<(String, Url)>: the service type and its absolute control URL
+fn find_wan_service(description: &str, location: &str) -> Result<(String, Url)> {
+    let (service_type, control_url) = description
+        .split("<service>")
+        .skip(1)
+        .find_map(|service| {
+            let service_type = xml_text(service, "serviceType")?;
+            if !WAN_SERVICE_TYPES.iter().any(|t| service_type.contains(t)) {
+                return None;
+            }
+            Some((service_type, xml_text(service, "controlURL")?))
+        })
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "No WAN connection service found"))?;
+
+    let base = match xml_text(description, "URLBase") {
+        Some(base) if !base.is_empty() => base,
+        _ => location.to_string(),
+    };
+    let control_url = Url::parse(&base)
+        .and_then(|base| base.join(&control_url))
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    Ok((service_type, control_url))
+}
+
+/// Get the address from a GetExternalIPAddress response
+fn parse_external_ip(response: &str) -> Result<Ipv4Addr> {
+    let ip = xml_text(response, "NewExternalIPAddress")
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "No external address in response"))?;
+    ip.parse::<Ipv4Addr>().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid external address: {}", ip),
+        )
+    })
+}
+
+/// Ask the gateway with a PCP MAP request, the assigned external address being the WAN address
+///
+/// PCP has no request that only reads the address, so this creates a mapping on the gateway
+/// and deletes it right after the response
+async fn get_ip_via_pcp(gateway: Ipv4Addr, timeout: Duration) -> Result<Ipv4Addr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket
+        .connect(SocketAddr::new(IpAddr::V4(gateway), NAT_PMP_PORT))
+        .await?;
+    let local_addr = match socket.local_addr()? {
+        SocketAddr::V4(addr) => addr,
+        SocketAddr::V6(_) => return Err(Error::other("Unexpected IPv6 local address")),
+    };
+
+    // map the (closed) local port for a short time, then delete the mapping
+    let nonce: [u8; 12] = rand::thread_rng().gen();
+    let request = |lifetime: u32| -> Vec<u8> {
+        let mut request = Vec::with_capacity(60);
+        // Header: version, opcode(MAP), reserved, lifetime, client address
+        request.extend_from_slice(&[2, 1, 0, 0]);
+        request.extend_from_slice(&lifetime.to_be_bytes());
+        request.extend_from_slice(&local_addr.ip().to_ipv6_mapped().octets());
+        // MAP: nonce, protocol(UDP), reserved, internal port, external port, external address
+        request.extend_from_slice(&nonce);
+        request.extend_from_slice(&[17, 0, 0, 0]);
+        request.extend_from_slice(&local_addr.port().to_be_bytes());
+        request.extend_from_slice(&0u16.to_be_bytes());
+        request.extend_from_slice(&Ipv6Addr::UNSPECIFIED.octets());
+        request
+    };
+
+    socket.send(&request(60)).await?;
+    let mut buffer = [0u8; 1100];
+    let size = tokio::time::timeout(timeout, socket.recv(&mut buffer))
+        .await
+        .map_err(|_| Error::new(ErrorKind::TimedOut, "PCP request timed out"))??;
+    let _ = socket.send(&request(0)).await;
+
+    parse_pcp_response(&buffer[..size], &nonce)
+}
+
+/// Get the assigned external address from a PCP MAP response
+fn parse_pcp_response(response: &[u8], nonce: &[u8; 12]) -> Result<Ipv4Addr> {
+    // Header: version, R|opcode, reserved, result code, lifetime, epoch, reserved
+    // MAP: nonce, protocol, reserved, internal port, external port, external address
+    if response.len() < 60 || response[0] != 2 || response[1] != 0x81 {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid PCP response"));
+    }
+    if response[3] != 0 {
+        return Err(Error::other(format!("PCP result code {}", response[3])));
+    }
+    if response[24..36] != nonce[..] {
+        return Err(Error::new(ErrorKind::InvalidData, "PCP nonce mismatch"));
+    }
+
+    let mut octets = [0u8; 16];
+    octets.copy_from_slice(&response[44..60]);
+    Ipv6Addr::from(octets)
+        .to_ipv4_mapped()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "PCP returned a non-IPv4 address"))
+}
+
+/// Ask the gateway with a NAT-PMP external address request
+async fn get_ip_via_nat_pmp(gateway: Ipv4Addr, timeout: Duration) -> Result<Ipv4Addr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket
+        .connect(SocketAddr::new(IpAddr::V4(gateway), NAT_PMP_PORT))
+        .await?;
+    // version 0, opcode 0
+    socket.send(&[0, 0]).await?;
+
+    let mut buffer = [0u8; 16];
+    let size = tokio::time::timeout(timeout, socket.recv(&mut buffer))
+        .await
+        .map_err(|_| Error::new(ErrorKind::TimedOut, "NAT-PMP request timed out"))??;
+
+    parse_nat_pmp_response(&buffer[..size])
+}
+
+/// Get the external address from a NAT-PMP external address response
+fn parse_nat_pmp_response(response: &[u8]) -> Result<Ipv4Addr> {
+    // version, opcode(128), result code, epoch, external address
+    if response.len() < 12 || response[0] != 0 || response[1] != 128 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Invalid NAT-PMP response",
+        ));
+    }
+    let result_code = u16::from_be_bytes([response[2], response[3]]);
+    if result_code != 0 {
+        return Err(Error::other(format!("NAT-PMP result code {}", result_code)));
+    }
+    Ok(Ipv4Addr::new(
+        response[8],
+        response[9],
+        response[10],
+        response[11],
+    ))
+}
+
+/// Get the text of the first `<tag>` element, ignoring namespace prefixes and attributes
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = rest.find('>')?;
+        let name = rest[..end].split_whitespace().next().unwrap_or_default();
+        let local_name = name.rsplit(':').next().unwrap_or(name);
+        if local_name == tag {
+            let content = &rest[end + 1..];
+            let close = content.find("</")?;
+            return Some(content[..close].trim().to_string());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:InternetGatewayDevice:1</deviceType>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>
+        <controlURL>/ctl/L3F</controlURL>
+      </service>
+    </serviceList>
+    <deviceList><device><deviceList><device>
+      <serviceList>
+        <service>
+          <serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>
+          <serviceId>urn:upnp-org:serviceId:WANIPConn1</serviceId>
+          <controlURL>/ctl/IPConn</controlURL>
+        </service>
+      </serviceList>
+    </device></deviceList></device></deviceList>
+  </device>
+</root>"#;
+
+    #[test]
+    fn ssdp_location() {
+        let response = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\n\
+                        ST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\
+                        Location: http://192.168.1.1:5000/rootDesc.xml\r\n\r\n";
+        assert_eq!(
+            parse_ssdp_location(response).as_deref(),
+            Some("http://192.168.1.1:5000/rootDesc.xml")
+        );
+        assert_eq!(
+            parse_ssdp_location("HTTP/1.1 200 OK\r\nST: upnp:rootdevice\r\n\r\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn xml_text_ignores_namespaces_and_attributes() {
+        let response = r#"<?xml version="1.0"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/">
+  <s:Body>
+    <u:GetExternalIPAddressResponse xmlns:u="urn:schemas-upnp-org:service:WANIPConnection:1">
+      <NewExternalIPAddress xmlns:dt="urn:schemas-microsoft-com:datatypes" dt:dt="string"> 203.0.113.7 </NewExternalIPAddress>
+    </u:GetExternalIPAddressResponse>
+  </s:Body>
+</s:Envelope>"#;
+        assert_eq!(
+            xml_text(response, "NewExternalIPAddress").as_deref(),
+            Some("203.0.113.7")
+        );
+        assert_eq!(
+            xml_text("<m:a><m:URLBase>http://x/</m:URLBase></m:a>", "URLBase").as_deref(),
+            Some("http://x/")
+        );
+        assert_eq!(xml_text("<a><b>text</b></a>", "c"), None);
+        // unterminated tags do not panic
+        assert_eq!(xml_text("<a><b", "b"), None);
+        assert_eq!(xml_text("<b>text", "b"), None);
+
+        assert_eq!(
+            parse_external_ip(response).unwrap(),
+            Ipv4Addr::new(203, 0, 113, 7)
+        );
+        assert!(parse_external_ip("<NewExternalIPAddress></NewExternalIPAddress>").is_err());
+        assert!(parse_external_ip("<s:Fault>701</s:Fault>").is_err());
+    }
+
+    #[test]
+    fn wan_service_from_description() {
+        let (service_type, control_url) =
+            find_wan_service(DESCRIPTION, "http://192.168.1.1:5000/rootDesc.xml").unwrap();
+        assert_eq!(
+            service_type,
+            "urn:schemas-upnp-org:service:WANIPConnection:1"
+        );
+        assert_eq!(control_url.as_str(), "http://192.168.1.1:5000/ctl/IPConn");
+
+        // URLBase takes precedence over the description location
+        let with_base = DESCRIPTION.replace(
+            "<device>",
+            "<URLBase>http://10.0.0.1:49152/</URLBase><device>",
+        );
+        let (_, control_url) =
+            find_wan_service(&with_base, "http://192.168.1.1:5000/rootDesc.xml").unwrap();
+        assert_eq!(control_url.as_str(), "http://10.0.0.1:49152/ctl/IPConn");
+
+        // PPP connection
+        let ppp = DESCRIPTION.replace("WANIPConnection", "WANPPPConnection");
+        assert!(find_wan_service(&ppp, "http://192.168.1.1/").is_ok());
+
+        // missing controlURL or WAN service
+        let no_control_url = DESCRIPTION.replace("<controlURL>/ctl/IPConn</controlURL>", "");
+        assert_eq!(
+            find_wan_service(&no_control_url, "http://192.168.1.1/")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+        let no_wan = DESCRIPTION.replace("WANIPConnection", "WANCommonInterfaceConfig");
+        assert!(find_wan_service(&no_wan, "http://192.168.1.1/").is_err());
+    }
+
+    fn nat_pmp_response(result_code: u16, address: [u8; 4]) -> Vec<u8> {
+        let mut response = vec![0, 128];
+        response.extend_from_slice(&result_code.to_be_bytes());
+        response.extend_from_slice(&1234u32.to_be_bytes());
+        response.extend_from_slice(&address);
+        response
+    }
+
+    #[test]
+    fn nat_pmp_responses() {
+        assert_eq!(
+            parse_nat_pmp_response(&nat_pmp_response(0, [203, 0, 113, 7])).unwrap(),
+            Ipv4Addr::new(203, 0, 113, 7)
+        );
+        // result code 3: network failure
+        assert!(parse_nat_pmp_response(&nat_pmp_response(3, [0; 4])).is_err());
+        // wrong opcode or version
+        let mut response = nat_pmp_response(0, [203, 0, 113, 7]);
+        response[1] = 129;
+        assert!(parse_nat_pmp_response(&response).is_err());
+        let mut response = nat_pmp_response(0, [203, 0, 113, 7]);
+        response[0] = 2;
+        assert!(parse_nat_pmp_response(&response).is_err());
+        for end in 0..12 {
+            assert!(parse_nat_pmp_response(&nat_pmp_response(0, [203, 0, 113, 7])[..end]).is_err());
+        }
+    }
+
+    fn pcp_response(result_code: u8, nonce: &[u8; 12], address: Ipv6Addr) -> Vec<u8> {
+        let mut response = vec![2, 0x81, 0, result_code];
+        response.extend_from_slice(&60u32.to_be_bytes());
+        response.extend_from_slice(&1234u32.to_be_bytes());
+        response.extend_from_slice(&[0; 12]);
+        response.extend_from_slice(nonce);
+        response.extend_from_slice(&[17, 0, 0, 0]);
+        response.extend_from_slice(&40000u16.to_be_bytes());
+        response.extend_from_slice(&40000u16.to_be_bytes());
+        response.extend_from_slice(&address.octets());
+        response
+    }
+
+    #[test]
+    fn pcp_responses() {
+        let nonce = [7u8; 12];
+        let mapped = Ipv4Addr::new(203, 0, 113, 7).to_ipv6_mapped();
+        assert_eq!(
+            parse_pcp_response(&pcp_response(0, &nonce, mapped), &nonce).unwrap(),
+            Ipv4Addr::new(203, 0, 113, 7)
+        );
+        // result code 8: NO_RESOURCES
+        assert!(parse_pcp_response(&pcp_response(8, &nonce, mapped), &nonce).is_err());
+        // nonce of another request
+        assert!(parse_pcp_response(&pcp_response(0, &[8u8; 12], mapped), &nonce).is_err());
+        // IPv6 external address
+        let v6 = "2001:db8::7".parse().unwrap();
+        assert!(parse_pcp_response(&pcp_response(0, &nonce, v6), &nonce).is_err());
+        // request instead of response
+        let mut response = pcp_response(0, &nonce, mapped);
+        response[1] = 0x01;
+        assert!(parse_pcp_response(&response, &nonce).is_err());
+        for end in 0..60 {
+            assert!(parse_pcp_response(&pcp_response(0, &nonce, mapped)[..end], &nonce).is_err());
+        }
+    }
+}
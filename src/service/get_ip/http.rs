@@ -0,0 +1,287 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use pnet::ipnetwork::IpNetwork;
+use tokio::task::JoinSet;
+use tracing::{debug, warn};
+
+use crate::config::{HttpDetect, IP};
+
+/// Get IP address from HTTP "what is my IP" services
+///
+/// An address is accepted only if at least `quorum` sources agree on it.
+pub async fn get_ip_via_http(http_detect: &HttpDetect) -> Option<IP> {
+    let timeout = Duration::from_secs(http_detect.timeout);
+    let required_votes = http_detect.required_votes();
+
+    let v4_addr = http_consensus(
+        http_detect,
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        timeout,
+        required_votes,
+    )
+    .await;
+    let v6_addr = http_consensus(
+        http_detect,
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        timeout,
+        required_votes,
+    )
+    .await;
+
+    if v4_addr.is_none() && v6_addr.is_none() {
+        None
+    } else {
+        Some(IP {
+            v4: v4_addr.map(IpNetwork::from),
+            v6: v6_addr.map(IpNetwork::from),
+        })
+    }
+}
+
+/// Query all HTTP sources of one address family concurrently and vote on the result
+async fn http_consensus(
+    http_detect: &HttpDetect,
+    local_addr: IpAddr,
+    timeout: Duration,
+    required_votes: usize,
+) -> Option<IpAddr> {
+    // bind the local address to force requests over the wanted address family
+    let client = match reqwest::Client::builder()
+        .local_address(local_addr)
+        .timeout(timeout)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to build HTTP client: {}", e);
+            return None;
+        }
+    };
+
+    let mut tasks = JoinSet::new();
+    for source in http_detect.sources.iter() {
+        let url = if local_addr.is_ipv4() {
+            source.url_v4.clone()
+        } else {
+            source.url_v6.clone()
+        };
+        let url = match url {
+            Some(url) => url,
+            None => continue,
+        };
+        let client = client.clone();
+        let name = source.name.clone();
+        let json_field = source.json_field.clone();
+        tasks.spawn(async move {
+            let result = fetch_ip_via_http(&client, &url, json_field.as_deref()).await;
+            (name, result)
+        });
+    }
+
+    if tasks.is_empty() {
+        return None;
+    }
+
+    let mut votes: HashMap<IpAddr, usize> = HashMap::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (name, result) = match joined {
+            Ok(joined) => joined,
+            Err(_) => continue,
+        };
+        match result {
+            Ok(ip) if ip.is_ipv4() == local_addr.is_ipv4() => {
+                debug!("HTTP source \"{}\" reported {}", name, ip);
+                *votes.entry(ip).or_default() += 1;
+            }
+            Ok(ip) => debug!(
+                "HTTP source \"{}\" reported {} of the wrong address family",
+                name, ip
+            ),
+            Err(e) => debug!("HTTP source \"{}\" failed: {}", name, e),
+        }
+    }
+
+    select_by_votes(&votes, required_votes)
+}
+
+/// Pick the address with the most votes if it reaches the quorum
+///
+/// A tie for the most votes is a disagreement and no address is accepted.
+fn select_by_votes(votes: &HashMap<IpAddr, usize>, required_votes: usize) -> Option<IpAddr> {
+    let (ip, count) = votes.iter().max_by_key(|(_, count)| **count)?;
+    if votes.values().filter(|other| *other == count).count() > 1 {
+        warn!("HTTP sources disagree without a majority: {:?}", votes);
+        None
+    } else if *count >= required_votes {
+        Some(*ip)
+    } else {
+        warn!(
+            "HTTP sources did not reach quorum ({} of {} required): {:?}",
+            count, required_votes, votes
+        );
+        None
+    }
+}
+
+/// Fetch an address from one HTTP source, the response being plain text or JSON
+async fn fetch_ip_via_http(
+    client: &reqwest::Client,
+    url: &str,
+    json_field: Option<&str>,
+) -> Result<IpAddr, String> {
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP status {}", response.status()));
+    }
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    parse_ip(&text, json_field)
+}
+
+/// Parse the address from a response body, plain text or a (nested) JSON field
+fn parse_ip(text: &str, json_field: Option<&str>) -> Result<IpAddr, String> {
+    let value = match json_field {
+        None => text.trim().to_string(),
+        Some(json_field) => {
+            let json: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+            let mut value = &json;
+            for field in json_field.split('.') {
+                value = value
+                    .get(field)
+                    .ok_or_else(|| format!("field \"{}\" not found", json_field))?;
+            }
+            value
+                .as_str()
+                .ok_or_else(|| format!("field \"{}\" is not a string", json_field))?
+                .trim()
+                .to_string()
+        }
+    };
+
+    value
+        .parse::<IpAddr>()
+        .map_err(|_| format!("invalid address \"{}\"", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::{config::HttpSource, util::dns_client::stub::block_on};
+
+    /// Start a local HTTP server answering every request with the body, returning its URL
+    async fn serve_http(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buffer = [0u8; 1024];
+                let _ = stream.read(&mut buffer).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    async fn detect(bodies: &[(&'static str, Option<&str>)], quorum: usize) -> Option<IpAddr> {
+        let mut sources = Vec::new();
+        for (index, (body, json_field)) in bodies.iter().enumerate() {
+            sources.push(HttpSource {
+                name: format!("stub{}", index),
+                url_v4: Some(serve_http(body).await),
+                url_v6: None,
+                json_field: json_field.map(String::from),
+            });
+        }
+        let http_detect = HttpDetect {
+            quorum,
+            timeout: 2,
+            sources,
+        };
+        http_consensus(
+            &http_detect,
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Duration::from_secs(2),
+            http_detect.required_votes(),
+        )
+        .await
+    }
+
+    #[test]
+    fn majority_wins() {
+        let ip = block_on(detect(
+            &[
+                ("203.0.113.7\n", None),
+                ("{\"ip\":\"203.0.113.7\"}", Some("ip")),
+                ("198.51.100.1", None),
+            ],
+            0,
+        ));
+        assert_eq!(ip, Some("203.0.113.7".parse().unwrap()));
+    }
+
+    #[test]
+    fn tie_is_rejected() {
+        let ip = block_on(detect(&[("203.0.113.7", None), ("198.51.100.1", None)], 1));
+        assert_eq!(ip, None);
+    }
+
+    #[test]
+    fn quorum_not_reached() {
+        // two of three agree, but all three are required
+        let ip = block_on(detect(
+            &[
+                ("203.0.113.7", None),
+                ("203.0.113.7", None),
+                ("not an address", None),
+            ],
+            3,
+        ));
+        assert_eq!(ip, None);
+    }
+
+    #[test]
+    fn select_votes() {
+        let a: IpAddr = "203.0.113.7".parse().unwrap();
+        let b: IpAddr = "198.51.100.1".parse().unwrap();
+        assert_eq!(
+            select_by_votes(&HashMap::from([(a, 2), (b, 1)]), 2),
+            Some(a)
+        );
+        assert_eq!(select_by_votes(&HashMap::from([(a, 2), (b, 2)]), 2), None);
+        assert_eq!(select_by_votes(&HashMap::from([(a, 1)]), 2), None);
+        assert_eq!(select_by_votes(&HashMap::new(), 1), None);
+    }
+
+    #[test]
+    fn json_field() {
+        let body = r#"{"data":{"client":{"ip":" 2001:db8::7 "}},"ip":5}"#;
+        assert_eq!(
+            parse_ip(body, Some("data.client.ip")),
+            Ok("2001:db8::7".parse().unwrap())
+        );
+        assert!(parse_ip(body, Some("data.server.ip"))
+            .unwrap_err()
+            .contains("not found"));
+        assert!(parse_ip(body, Some("ip"))
+            .unwrap_err()
+            .contains("not a string"));
+        assert!(parse_ip("<html>", Some("ip")).is_err());
+        assert_eq!(
+            parse_ip("203.0.113.7\n", None),
+            Ok("203.0.113.7".parse().unwrap())
+        );
+    }
+}
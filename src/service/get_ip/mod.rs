@@ -0,0 +1,473 @@
+mod command;
+mod dns;
+mod file;
+mod gateway;
+mod http;
+mod nic;
+mod route;
+mod select;
+mod socket;
+mod stun;
+mod validate;
+
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use pnet::ipnetwork::IpNetwork;
+use select::AddressSelector;
+use tracing::{debug, warn};
+use validate::AddressValidator;
+
+use crate::config::{
+    select::AddressSuffix,
+    source::{NicSelector, SourceSpec},
+    CommandSource, Config, DnsDetectQuery, FileSource, HttpDetect, MonitorRecord, IP,
+};
+
+/// 来源返回的地址及其属性
+#[derive(Debug, Clone)]
+pub struct SourceAddress {
+    pub ip: IpNetwork,
+    /// RFC 4941 临时地址
+    pub temporary: bool,
+    /// IPv4从地址
+    pub secondary: bool,
+    /// 已弃用（首选生存期已过）的地址
+    pub deprecated: bool,
+    /// 由该地址生成临时地址（IFA_F_MANAGETEMPADDR）
+    pub manages_temporary: bool,
+    /// 首选生存期（单位：秒），`u32::MAX` 为无限
+    pub preferred_lifetime: u32,
+    /// 有效生存期（单位：秒），`u32::MAX` 为无限
+    pub valid_lifetime: u32,
+}
+
+impl From<IpNetwork> for SourceAddress {
+    fn from(ip: IpNetwork) -> Self {
+        SourceAddress {
+            ip,
+            temporary: false,
+            secondary: false,
+            deprecated: false,
+            manages_temporary: false,
+            preferred_lifetime: u32::MAX,
+            valid_lifetime: u32::MAX,
+        }
+    }
+}
+
+/// IP地址来源
+#[async_trait]
+pub trait IpSource: Send + Sync {
+    /// 获取该来源当前的全部地址
+    async fn get_ip(&self) -> Result<Vec<SourceAddress>>;
+}
+
+/// 网卡上的地址
+struct NicSource {
+    selector: NicSelector,
+}
+
+#[async_trait]
+impl IpSource for NicSource {
+    async fn get_ip(&self) -> Result<Vec<SourceAddress>> {
+        nic::get_ip_via_nic(&self.selector).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No NIC matches \"{}\"", self.selector),
+            )
+        })
+    }
+}
+
+/// 向外连接的UDP Socket的本地地址
+struct SocketSource {
+    probe_v4: String,
+    probe_v6: String,
+}
+
+#[async_trait]
+impl IpSource for SocketSource {
+    async fn get_ip(&self) -> Result<Vec<SourceAddress>> {
+        ip_to_list(socket::get_ip_via_socket(&self.probe_v4, &self.probe_v6).await)
+    }
+}
+
+/// 主路由表中默认路由的出口地址
+struct RouteSource;
+
+#[async_trait]
+impl IpSource for RouteSource {
+    async fn get_ip(&self) -> Result<Vec<SourceAddress>> {
+        route::get_ip_via_route()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No default route found"))
+    }
+}
+
+/// HTTP服务的一致结果
+struct HttpSource {
+    http_detect: HttpDetect,
+}
+
+#[async_trait]
+impl IpSource for HttpSource {
+    async fn get_ip(&self) -> Result<Vec<SourceAddress>> {
+        ip_to_list(http::get_ip_via_http(&self.http_detect).await)
+    }
+}
+
+/// STUN服务器
+struct StunSource {
+    servers: Vec<String>,
+    timeout: Duration,
+}
+
+#[async_trait]
+impl IpSource for StunSource {
+    async fn get_ip(&self) -> Result<Vec<SourceAddress>> {
+        ip_to_list(stun::get_ip_via_stun(&self.servers, self.timeout).await)
+    }
+}
+
+/// DNS查询
+struct DnsSource {
+    queries: Vec<DnsDetectQuery>,
+    timeout: Duration,
+}
+
+#[async_trait]
+impl IpSource for DnsSource {
+    async fn get_ip(&self) -> Result<Vec<SourceAddress>> {
+        ip_to_list(dns::get_ip_via_dns(&self.queries, self.timeout).await)
+    }
+}
+
+/// 网关（UPnP IGD / PCP / NAT-PMP）
+struct GatewaySource {
+    gateway: Option<Ipv4Addr>,
+    pcp: bool,
+    timeout: Duration,
+}
+
+#[async_trait]
+impl IpSource for GatewaySource {
+    async fn get_ip(&self) -> Result<Vec<SourceAddress>> {
+        ip_to_list(gateway::get_ip_via_gateway(self.gateway, self.pcp, self.timeout).await)
+    }
+}
+
+/// 外部命令的输出
+struct CommandIpSource {
+    command: CommandSource,
+    timeout: Duration,
+}
+
+#[async_trait]
+impl IpSource for CommandIpSource {
+    async fn get_ip(&self) -> Result<Vec<SourceAddress>> {
+        command::get_ip_via_command(&self.command, self.timeout).await
+    }
+}
+
+/// 由其他程序写入的文件
+struct FileIpSource {
+    file: FileSource,
+    max_age: Duration,
+}
+
+#[async_trait]
+impl IpSource for FileIpSource {
+    async fn get_ip(&self) -> Result<Vec<SourceAddress>> {
+        file::get_ip_via_file(&self.file, self.max_age)
+    }
+}
+
+/// 按来源描述构造IP来源
+fn build_source(config: &Config, spec: &SourceSpec) -> Box<dyn IpSource> {
+    match spec {
+        SourceSpec::Nic(selector) => Box::new(NicSource {
+            selector: selector.clone(),
+        }),
+        SourceSpec::Socket => Box::new(SocketSource {
+            probe_v4: config.socket_detect.probe_v4.clone(),
+            probe_v6: config.socket_detect.probe_v6.clone(),
+        }),
+        SourceSpec::Route => Box::new(RouteSource),
+        SourceSpec::Http(None) => Box::new(HttpSource {
+            http_detect: config.http_detect.clone(),
+        }),
+        SourceSpec::Http(Some(name)) => {
+            // 仅使用指定的HTTP服务，无需投票
+            let mut http_detect = config.http_detect.clone();
+            http_detect.sources.retain(|source| source.name == *name);
+            http_detect.quorum = 1;
+            Box::new(HttpSource { http_detect })
+        }
+        SourceSpec::Stun => Box::new(StunSource {
+            servers: config.stun_detect.servers.clone(),
+            timeout: Duration::from_secs(config.stun_detect.timeout),
+        }),
+        SourceSpec::Dns => Box::new(DnsSource {
+            queries: config.dns_detect.queries.clone(),
+            timeout: Duration::from_secs(config.dns_detect.timeout),
+        }),
+        SourceSpec::Upnp => Box::new(GatewaySource {
+            gateway: config
+                .gateway_detect
+                .gateway
+                .as_ref()
+                .and_then(|gateway| gateway.parse().ok()),
+            pcp: config.gateway_detect.pcp,
+            timeout: Duration::from_secs(config.gateway_detect.timeout),
+        }),
+        SourceSpec::Command(name) => Box::new(CommandIpSource {
+            // 配置检查已确保命令存在
+            command: config
+                .command_detect
+                .commands
+                .iter()
+                .find(|command| command.name == *name)
+                .cloned()
+                .unwrap(),
+            timeout: Duration::from_secs(config.command_detect.timeout),
+        }),
+        SourceSpec::File(name) => Box::new(FileIpSource {
+            file: config
+                .file_detect
+                .files
+                .iter()
+                .find(|file| file.name == *name)
+                .cloned()
+                .unwrap(),
+            max_age: Duration::from_secs(config.file_detect.max_age),
+        }),
+    }
+}
+
+fn ip_to_list(ip: Option<IP>) -> Result<Vec<SourceAddress>> {
+    match ip {
+        Some(ip) => Ok(ip
+            .v4
+            .into_iter()
+            .chain(ip.v6)
+            .map(SourceAddress::from)
+            .collect()),
+        None => Err(Error::new(ErrorKind::NotFound, "No IP address found")),
+    }
+}
+
+/// 从文本中提取地址，每个以空白或逗号分隔的词为一个地址（可带前缀长度）
+///
+/// 每行`#`之后为注释；未指定、回环、组播及广播地址不能作为公网地址，将被忽略
+fn parse_ip_list(text: &str) -> Result<Vec<SourceAddress>> {
+    let ips: Vec<SourceAddress> = text
+        .lines()
+        .map(|line| line.split_once('#').map_or(line, |(line, _)| line))
+        .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+        .filter_map(|word| {
+            let word = word.trim_matches(|c: char| c == '"' || c == '\'' || c == ';');
+            match word.split_once('/') {
+                Some((ip, prefix)) => IpNetwork::new(ip.parse().ok()?, prefix.parse().ok()?).ok(),
+                None => word.parse::<IpAddr>().ok().map(IpNetwork::from),
+            }
+        })
+        .filter(|ip| {
+            let usable = match ip.ip() {
+                IpAddr::V4(ip) => {
+                    !(ip.is_unspecified()
+                        || ip.is_loopback()
+                        || ip.is_multicast()
+                        || ip.is_broadcast())
+                }
+                IpAddr::V6(ip) => !(ip.is_unspecified() || ip.is_loopback() || ip.is_multicast()),
+            };
+            if !usable {
+                debug!("Ignore unusable address {}", ip);
+            }
+            usable
+        })
+        .map(SourceAddress::from)
+        .collect();
+
+    if ips.is_empty() {
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            "No valid IP address found",
+        ))
+    } else {
+        Ok(ips)
+    }
+}
+
+/// 全部记录所用的IP来源
+pub struct IpSources {
+    sources: HashMap<SourceSpec, Box<dyn IpSource>>,
+    /// 构建来源时的配置，检测时按其中的来源列表查找，与重新加载的配置互不影响
+    config: Arc<Config>,
+}
+
+impl IpSources {
+    pub fn new(config: Arc<Config>) -> IpSources {
+        let mut sources = HashMap::new();
+        for record in config.records.iter() {
+            for spec in config.record_sources(record) {
+                sources
+                    .entry(spec.clone())
+                    .or_insert_with(|| build_source(&config, &spec));
+            }
+        }
+        IpSources { sources, config }
+    }
+
+    /// 前缀委派模式下，以地址的前缀与记录的接口标识组合成记录值
+    fn apply_suffix(record: &MonitorRecord, ip: IpNetwork) -> IpNetwork {
+        // 配置检查已确保接口标识合法且仅用于AAAA记录
+        let suffix = match record
+            .suffix
+            .as_ref()
+            .and_then(|s| s.parse::<AddressSuffix>().ok())
+        {
+            Some(suffix) => suffix,
+            None => return ip,
+        };
+        match ip.ip() {
+            IpAddr::V6(prefix) => {
+                let address = suffix.apply(prefix, record.prefix_length);
+                debug!(
+                    "Record \"{}\" combines prefix {}/{} with suffix to {}",
+                    record.hostname, prefix, record.prefix_length, address
+                );
+                IpNetwork::from(IpAddr::V6(address))
+            }
+            IpAddr::V4(_) => ip,
+        }
+    }
+
+    /// 按顺序尝试记录的IP来源，得到记录应指向的地址及其来源，未通过发布前检查的地址将被跳过
+    ///
+    /// # Arguments
+    ///     record: 解析记录
+    ///     cache: 本轮检查中各来源的结果，同一来源在一轮检查中只查询一次
+    pub async fn detect(
+        &self,
+        record: &MonitorRecord,
+        cache: &mut HashMap<SourceSpec, Option<Vec<SourceAddress>>>,
+    ) -> Option<(IpNetwork, SourceSpec)> {
+        let want_v4 = record.record_type == "A";
+        let selector = AddressSelector::new(record);
+        let validator = AddressValidator::new(record);
+
+        for spec in self.config.record_sources(record) {
+            if !cache.contains_key(&spec) {
+                let result = match self.sources.get(&spec) {
+                    Some(source) => match source.get_ip().await {
+                        Ok(ips) => Some(ips),
+                        Err(e) => {
+                            warn!("Failed to get IP from source \"{}\": {}", spec, e);
+                            None
+                        }
+                    },
+                    None => {
+                        warn!(
+                            "Source \"{}\" of record \"{}\" ({}) is not configured",
+                            spec, record.hostname, record.record_type
+                        );
+                        None
+                    }
+                };
+                cache.insert(spec.clone(), result);
+            }
+
+            // 同一来源有多个地址时，按记录的选择规则选出一个
+            let ip = cache
+                .get(&spec)
+                .and_then(|ips| ips.as_ref())
+                .and_then(|ips| selector.select(ips, want_v4));
+            match ip {
+                Some(ip) => {
+                    debug!(
+                        "Record \"{}\" ({}) got {} from source \"{}\"",
+                        record.hostname, record.record_type, ip, spec
+                    );
+                    let ip = Self::apply_suffix(record, ip);
+                    // 发布前检查地址，不合格时尝试下一个来源
+                    match validator.check(record, ip.ip()) {
+                        Ok(()) => return Some((ip, spec)),
+                        Err(reason) => warn!(
+                            "Refusing to publish the address from source \"{}\" for record \"{}\" ({}): {}",
+                            spec, record.hostname, record.record_type, reason
+                        ),
+                    }
+                }
+                None => debug!(
+                    "Source \"{}\" has no {} address for record \"{}\", trying next",
+                    spec, record.record_type, record.hostname
+                ),
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ips(text: &str) -> Vec<String> {
+        parse_ip_list(text)
+            .unwrap()
+            .into_iter()
+            .map(|address| address.ip.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_mixed_list() {
+        let text = "# written by pppd\n\
+                    \n\
+                    203.0.113.7\n\
+                    \t2001:db8::7/64  \n\
+                    # 198.51.100.1\n\
+                    \"192.0.2.1\", '2001:db8::8';\n\
+                    2001:db8::9 # trailing comment 192.0.2.99\n";
+        assert_eq!(
+            ips(text),
+            [
+                "203.0.113.7/32",
+                "2001:db8::7/64",
+                "192.0.2.1/32",
+                "2001:db8::8/128",
+                "2001:db8::9/128"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_ignores_garbage() {
+        let text = "inet 203.0.113.7/24 brd 203.0.113.255 scope global\n\
+                    256.1.1.1 1.2.3 2001:db8::zz 192.0.2.1/33 192.0.2.2/x\n\
+                    0.0.0.0 127.0.0.1 224.0.0.1 255.255.255.255 :: ::1 ff02::1\n";
+        assert_eq!(ips(text), ["203.0.113.7/24", "203.0.113.255/32"]);
+    }
+
+    #[test]
+    fn parse_without_addresses() {
+        for text in [
+            "",
+            "\n\n",
+            "# 203.0.113.7\n",
+            "error: no address\n",
+            "127.0.0.1 ::1",
+        ] {
+            let err = parse_ip_list(text).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{text:?}");
+        }
+    }
+}
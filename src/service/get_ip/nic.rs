@@ -0,0 +1,176 @@
+use pnet::{datalink::NetworkInterface, util::MacAddr};
+use tracing::debug;
+
+use crate::{
+    config::source::{nic_pattern_regex, NicSelector},
+    util::route::{default_routes_v4, default_routes_v6},
+};
+
+use super::SourceAddress;
+
+/// A selected NIC and which address families to take from it
+struct MatchedInterface {
+    interface: NetworkInterface,
+    v4: bool,
+    v6: bool,
+}
+
+/// Get all IP addresses of the NICs chosen by the selector
+///
+/// The NICs are resolved on every call, so renamed or re-created interfaces are picked up.
+/// On Linux the addresses are read over rtnetlink together with their flags and lifetimes,
+/// falling back to `pnet` (without flags) if that fails. Returns None if no NIC matches.
+pub fn get_ip_via_nic(selector: &NicSelector) -> Option<Vec<SourceAddress>> {
+    let interfaces = pnet::datalink::interfaces();
+
+    debug!("Interfaces: {:?}", interfaces);
+
+    let matched = match_interfaces(selector, interfaces);
+    if matched.is_empty() {
+        return None;
+    }
+    debug!(
+        "NIC {} resolved to: {:?}",
+        selector,
+        matched
+            .iter()
+            .map(|matched| matched.interface.name.as_str())
+            .collect::<Vec<&str>>()
+    );
+
+    #[cfg(target_os = "linux")]
+    match get_ip_via_netlink(&matched) {
+        Ok(addresses) => return Some(addresses),
+        Err(e) => debug!(
+            "Failed to get addresses via netlink, fall back to pnet: {}",
+            e
+        ),
+    }
+
+    Some(
+        matched
+            .into_iter()
+            .flat_map(|matched| {
+                let (v4, v6) = (matched.v4, matched.v6);
+                matched
+                    .interface
+                    .ips
+                    .into_iter()
+                    .filter(move |ip| if ip.is_ipv4() { v4 } else { v6 })
+                    .map(SourceAddress::from)
+            })
+            .collect(),
+    )
+}
+
+/// Resolve the selector against the current interfaces, sorted by name
+fn match_interfaces(
+    selector: &NicSelector,
+    interfaces: Vec<NetworkInterface>,
+) -> Vec<MatchedInterface> {
+    let both = |interface| MatchedInterface {
+        interface,
+        v4: true,
+        v6: true,
+    };
+
+    let mut matched: Vec<MatchedInterface> = match selector {
+        NicSelector::Name(name) => interfaces
+            .into_iter()
+            .filter(|interface| interface.name == *name)
+            .map(both)
+            .collect(),
+        NicSelector::Mac(mac) => {
+            // 配置检查已确保MAC地址合法
+            let mac = mac.parse::<MacAddr>().ok();
+            interfaces
+                .into_iter()
+                .filter(|interface| interface.mac.is_some() && interface.mac == mac)
+                .map(both)
+                .collect()
+        }
+        NicSelector::Pattern(pattern) => match nic_pattern_regex(pattern) {
+            Ok(regex) => interfaces
+                .into_iter()
+                .filter(|interface| regex.is_match(&interface.name))
+                .map(both)
+                .collect(),
+            Err(_) => Vec::new(),
+        },
+        NicSelector::DefaultRoute => {
+            // IPv4与IPv6的默认路由可能位于不同的网卡
+            let v4_interface = default_routes_v4().into_iter().next().map(|r| r.interface);
+            let v6_interface = default_routes_v6().into_iter().next().map(|r| r.interface);
+            interfaces
+                .into_iter()
+                .filter_map(|interface| {
+                    let v4 = v4_interface.as_ref() == Some(&interface.name);
+                    let v6 = v6_interface.as_ref() == Some(&interface.name);
+                    if v4 || v6 {
+                        Some(MatchedInterface { interface, v4, v6 })
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        }
+    };
+
+    matched.sort_by(|a, b| a.interface.name.cmp(&b.interface.name));
+    matched
+}
+
+/// Get the addresses of the NICs over rtnetlink
+#[cfg(target_os = "linux")]
+fn get_ip_via_netlink(matched: &[MatchedInterface]) -> std::io::Result<Vec<SourceAddress>> {
+    let addresses = crate::util::netlink::get_addresses()?;
+    Ok(matched
+        .iter()
+        .flat_map(|matched| {
+            addresses
+                .iter()
+                .filter(|address| address.index == matched.interface.index)
+                .filter(|address| {
+                    if address.address.is_ipv4() {
+                        matched.v4
+                    } else {
+                        matched.v6
+                    }
+                })
+                .filter_map(|address| {
+                    debug!("Address on {}: {:?}", matched.interface.name, address);
+                    to_source_address(address)
+                })
+        })
+        .collect())
+}
+
+/// Convert an rtnetlink address, carrying over its flags and lifetimes
+///
+/// Tentative and DAD-failed addresses cannot be used yet and are skipped.
+/// the define of temporary address: https://tools.ietf.org/html/rfc4941
+#[cfg(target_os = "linux")]
+pub fn to_source_address(
+    address: &crate::util::netlink::InterfaceAddress,
+) -> Option<SourceAddress> {
+    use pnet::ipnetwork::IpNetwork;
+
+    use crate::util::netlink::{
+        IFA_F_DADFAILED, IFA_F_DEPRECATED, IFA_F_MANAGETEMPADDR, IFA_F_SECONDARY, IFA_F_TEMPORARY,
+        IFA_F_TENTATIVE,
+    };
+
+    if address.flags & (IFA_F_TENTATIVE | IFA_F_DADFAILED) != 0 {
+        return None;
+    }
+    let ip = IpNetwork::new(address.address, address.prefix_len).ok()?;
+    Some(SourceAddress {
+        ip,
+        temporary: ip.is_ipv6() && address.flags & IFA_F_TEMPORARY != 0,
+        secondary: ip.is_ipv4() && address.flags & IFA_F_SECONDARY != 0,
+        deprecated: address.flags & IFA_F_DEPRECATED != 0,
+        manages_temporary: address.flags & IFA_F_MANAGETEMPADDR != 0,
+        preferred_lifetime: address.preferred_lifetime,
+        valid_lifetime: address.valid_lifetime,
+    })
+}
@@ -0,0 +1,64 @@
+use tracing::debug;
+
+use crate::config::source::NicSelector;
+
+use super::{nic, SourceAddress};
+
+/// Get the egress addresses from the default routes in the main routing table
+///
+/// Unlike `get_ip_via_socket` no packet is sent. On Linux the routes are read over rtnetlink:
+/// the preferred source address (`src`) of the route is used if set, otherwise all addresses
+/// of the egress interface. Elsewhere, or if that fails, the addresses of the interface holding
+/// the default route in `/proc/net/route` and `/proc/net/ipv6_route` are used.
+pub fn get_ip_via_route() -> Option<Vec<SourceAddress>> {
+    #[cfg(target_os = "linux")]
+    match get_ip_via_netlink() {
+        Ok(addresses) if !addresses.is_empty() => return Some(addresses),
+        Ok(_) => debug!("No default route found via netlink"),
+        Err(e) => debug!(
+            "Failed to get routes via netlink, fall back to procfs: {}",
+            e
+        ),
+    }
+
+    nic::get_ip_via_nic(&NicSelector::DefaultRoute)
+}
+
+#[cfg(target_os = "linux")]
+fn get_ip_via_netlink() -> std::io::Result<Vec<SourceAddress>> {
+    use pnet::ipnetwork::IpNetwork;
+
+    use crate::util::netlink::{get_addresses, get_default_routes};
+
+    let addresses = get_addresses()?;
+    let mut result = Vec::new();
+    for ipv6 in [false, true] {
+        let route = match get_default_routes(ipv6)?.into_iter().next() {
+            Some(route) => route,
+            None => continue,
+        };
+        debug!(
+            "Default route via {:?} on interface {} (metric {})",
+            route.gateway, route.index, route.priority
+        );
+
+        match route.preferred_source {
+            Some(source) => result.push(
+                addresses
+                    .iter()
+                    .find(|address| address.address == source)
+                    .and_then(nic::to_source_address)
+                    .unwrap_or_else(|| SourceAddress::from(IpNetwork::from(source))),
+            ),
+            None => result.extend(
+                addresses
+                    .iter()
+                    .filter(|address| {
+                        address.index == route.index && address.address.is_ipv6() == ipv6
+                    })
+                    .filter_map(nic::to_source_address),
+            ),
+        }
+    }
+    Ok(result)
+}
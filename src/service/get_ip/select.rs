@@ -0,0 +1,294 @@
+use std::{cmp::Reverse, net::IpAddr};
+
+use pnet::ipnetwork::IpNetwork;
+use tracing::trace;
+
+use crate::config::{
+    select::{is_global, AddressPolicy, AddressRange, AddressScope},
+    MonitorRecord,
+};
+
+use super::SourceAddress;
+
+/// 记录的地址选择规则
+///
+/// 依次按以下规则过滤：地址族、作用域、`exclude`、`include`，
+/// 再按以下顺序排序并取第一个：`prefer` 中的顺序、全局地址优先（链路本地及回环地址最后）、未弃用优先、
+/// 地址选择策略、首选及有效生存期较长的优先、IPv4主地址优先、来源中的顺序
+pub struct AddressSelector {
+    scope: AddressScope,
+    exclude: Vec<AddressRange>,
+    include: Vec<AddressRange>,
+    prefer: Vec<AddressRange>,
+    policy: AddressPolicy,
+}
+
+impl AddressSelector {
+    pub fn new(record: &MonitorRecord) -> AddressSelector {
+        // 配置检查已确保规则合法
+        let parse = |ranges: &[String]| -> Vec<AddressRange> {
+            ranges
+                .iter()
+                .filter_map(|range| range.parse().ok())
+                .collect()
+        };
+        AddressSelector {
+            scope: record.scope.parse().unwrap_or(AddressScope::Any),
+            exclude: parse(&record.exclude),
+            include: parse(&record.include),
+            prefer: parse(&record.prefer),
+            policy: record.address_policy().unwrap_or(AddressPolicy::Stable),
+        }
+    }
+
+    /// 从来源返回的地址中选出一个
+    ///
+    /// # Arguments
+    ///     addresses: 来源返回的地址
+    ///     want_v4: 是否选择IPv4地址（否则选择IPv6地址）
+    pub fn select(&self, addresses: &[SourceAddress], want_v4: bool) -> Option<IpNetwork> {
+        addresses
+            .iter()
+            .enumerate()
+            .filter(|(_, address)| address.ip.is_ipv4() == want_v4)
+            .filter(|(_, address)| {
+                let ip = address.ip.ip();
+                let accepted = (self.scope == AddressScope::Any || is_global(ip))
+                    && !self.exclude.iter().any(|range| range.contains(ip))
+                    && (self.include.is_empty()
+                        || self.include.iter().any(|range| range.contains(ip)));
+                if !accepted {
+                    trace!("Address {} is filtered out", address.ip);
+                }
+                accepted
+            })
+            .min_by_key(|(index, address)| {
+                let ip = address.ip.ip();
+                let preference = self
+                    .prefer
+                    .iter()
+                    .position(|range| range.contains(ip))
+                    .unwrap_or(self.prefer.len());
+                // 策略：stable 优先非临时地址及生成临时地址的地址，temporary 优先临时地址，
+                // longest-lifetime 仅比较生存期
+                let policy_rank = match self.policy {
+                    AddressPolicy::Stable => (address.temporary, !address.manages_temporary),
+                    AddressPolicy::Temporary => (!address.temporary, false),
+                    AddressPolicy::LongestLifetime => (false, false),
+                };
+                (
+                    preference,
+                    scope_rank(ip),
+                    address.deprecated,
+                    policy_rank,
+                    Reverse(address.preferred_lifetime),
+                    Reverse(address.valid_lifetime),
+                    address.secondary,
+                    *index,
+                )
+            })
+            .map(|(_, address)| address.ip)
+    }
+}
+
+/// 作用域排序：全局地址、私有及唯一本地地址、链路本地地址、回环地址
+fn scope_rank(ip: IpAddr) -> u8 {
+    if is_global(ip) {
+        0
+    } else if ip.is_loopback() {
+        3
+    } else if AddressRange::LinkLocal.contains(ip) {
+        2
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+
+    use super::*;
+
+    fn address(ip: &str, build: impl FnOnce(&mut SourceAddress)) -> SourceAddress {
+        let mut address = SourceAddress::from(ip.parse::<IpNetwork>().unwrap());
+        build(&mut address);
+        address
+    }
+
+    fn addresses() -> Vec<SourceAddress> {
+        vec![
+            address("::1/128", |_| {}),
+            address("fe80::1/64", |_| {}),
+            address("fd00::1/64", |_| {}),
+            address("2001:db8:3::1/64", |a| {
+                a.deprecated = true;
+                a.preferred_lifetime = 0;
+            }),
+            address("2001:db8:3::2/64", |a| {
+                a.preferred_lifetime = 600;
+                a.valid_lifetime = 1200;
+            }),
+            address("2001:db8:1::abcd/64", |a| {
+                a.temporary = true;
+                a.preferred_lifetime = 1800;
+                a.valid_lifetime = 7200;
+            }),
+            address("2001:db8:1::beef/64", |a| {
+                a.temporary = true;
+                a.preferred_lifetime = 3000;
+                a.valid_lifetime = 7200;
+            }),
+            address("2001:db8:1::10/64", |a| {
+                a.manages_temporary = true;
+                a.preferred_lifetime = 3600;
+                a.valid_lifetime = 7200;
+            }),
+            address("2001:db8:2::1/64", |_| {}),
+            address("169.254.1.1/16", |_| {}),
+            address("192.168.1.2/24", |_| {}),
+            address("203.0.113.7/24", |a| a.secondary = true),
+            address("198.51.100.7/24", |_| {}),
+        ]
+    }
+
+    #[test]
+    fn select_address() {
+        // (record options, IPv4, selected address)
+        let cases: &[(&str, bool, Option<&str>)] = &[
+            // stable: the address generating temporary addresses, then static ones
+            ("", false, Some("2001:db8:1::10/64")),
+            (
+                "address-policy = \"stable\"",
+                false,
+                Some("2001:db8:1::10/64"),
+            ),
+            // temporary: the temporary address with the longest preferred lifetime
+            (
+                "address-policy = \"temporary\"",
+                false,
+                Some("2001:db8:1::beef/64"),
+            ),
+            (
+                "use-temporary-addr = true",
+                false,
+                Some("2001:db8:1::beef/64"),
+            ),
+            // longest-lifetime: only lifetimes count
+            (
+                "address-policy = \"longest-lifetime\"",
+                false,
+                Some("2001:db8:2::1/64"),
+            ),
+            // deprecated addresses come after valid ones in the same scope
+            (
+                "exclude = [\"2001:db8:1::/48\", \"2001:db8:2::/48\"]",
+                false,
+                Some("2001:db8:3::2/64"),
+            ),
+            (
+                "exclude = [\"2001:db8:1::/48\", \"2001:db8:2::/48\", \"2001:db8:3::2/128\"]",
+                false,
+                Some("2001:db8:3::1/64"),
+            ),
+            // scope order: ULA, link-local, loopback
+            ("exclude = [\"2001:db8::/32\"]", false, Some("fd00::1/64")),
+            (
+                "exclude = [\"2001:db8::/32\", \"ula\"]",
+                false,
+                Some("fe80::1/64"),
+            ),
+            (
+                "exclude = [\"2001:db8::/32\", \"ula\", \"link-local\"]",
+                false,
+                Some("::1/128"),
+            ),
+            (
+                "scope = \"global\"\nexclude = [\"2001:db8::/32\"]",
+                false,
+                None,
+            ),
+            // include
+            ("include = [\"ula\"]", false, Some("fd00::1/64")),
+            ("scope = \"global\"\ninclude = [\"ula\"]", false, None),
+            (
+                "include = [\"2001:db8:3::/48\"]",
+                false,
+                Some("2001:db8:3::2/64"),
+            ),
+            // exclude takes precedence over include
+            (
+                "include = [\"2001:db8::/32\"]\nexclude = [\"2001:db8:1::/48\"]",
+                false,
+                Some("2001:db8:2::1/64"),
+            ),
+            // prefer in order, before scope and policy
+            (
+                "prefer = [\"2001:db8:2::/48\"]",
+                false,
+                Some("2001:db8:2::1/64"),
+            ),
+            (
+                "prefer = [\"ula\", \"2001:db8:2::/48\"]",
+                false,
+                Some("fd00::1/64"),
+            ),
+            (
+                "address-policy = \"temporary\"\nprefer = [\"2001:db8:1::10/128\"]",
+                false,
+                Some("2001:db8:1::10/64"),
+            ),
+            // IPv4: primary before secondary, private before link-local
+            ("", true, Some("198.51.100.7/24")),
+            (
+                "exclude = [\"198.51.100.0/24\"]",
+                true,
+                Some("203.0.113.7/24"),
+            ),
+            ("include = [\"private\"]", true, Some("192.168.1.2/24")),
+            (
+                "exclude = [\"documentation\"]",
+                true,
+                Some("192.168.1.2/24"),
+            ),
+            (
+                "exclude = [\"documentation\", \"private\"]",
+                true,
+                Some("169.254.1.1/16"),
+            ),
+            ("scope = \"global\"\ninclude = [\"private\"]", true, None),
+            ("include = [\"2001:db8::/32\"]", true, None),
+        ];
+
+        let addresses = addresses();
+        for (options, want_v4, expected) in cases {
+            let config = Config::for_test(&format!(
+                "[[record]]\nrecord-type = \"AAAA\"\nhostname = \"www\"\n{}",
+                options
+            ));
+            let selected = AddressSelector::new(&config.records[0])
+                .select(&addresses, *want_v4)
+                .map(|ip| ip.to_string());
+            assert_eq!(
+                selected.as_deref(),
+                *expected,
+                "{options:?}, IPv4: {want_v4}"
+            );
+        }
+    }
+
+    #[test]
+    fn keep_source_order_on_ties() {
+        let config = Config::for_test("[[record]]\nrecord-type = \"A\"\nhostname = \"www\"");
+        let selector = AddressSelector::new(&config.records[0]);
+        let addresses = [
+            address("203.0.113.7/24", |_| {}),
+            address("198.51.100.7/24", |_| {}),
+        ];
+        assert_eq!(
+            selector.select(&addresses, true).unwrap().to_string(),
+            "203.0.113.7/24"
+        );
+        assert_eq!(selector.select(&[], true), None);
+    }
+}
@@ -0,0 +1,36 @@
+use pnet::ipnetwork::IpNetwork;
+use tokio::net::UdpSocket;
+use tracing::debug;
+
+use crate::config::IP;
+
+/// Get IP address from UDP socket
+///
+/// Connecting a UDP socket sends no packet, the kernel only picks the local address
+/// it would use to reach the probe target.
+pub async fn get_ip_via_socket(probe_v4: &str, probe_v6: &str) -> Option<IP> {
+    let v4_addr = local_addr("0.0.0.0:0", probe_v4).await;
+    let v6_addr = local_addr("[::]:0", probe_v6).await;
+
+    if v4_addr.is_none() && v6_addr.is_none() {
+        None
+    } else {
+        Some(IP {
+            v4: v4_addr,
+            v6: v6_addr,
+        })
+    }
+}
+
+/// Local address of a UDP socket connected to the target
+async fn local_addr(bind_addr: &str, connect_addr: &str) -> Option<IpNetwork> {
+    let socket = UdpSocket::bind(bind_addr).await.ok()?;
+    if let Err(e) = socket.connect(connect_addr).await {
+        debug!("Failed to connect UDP socket to {}: {}", connect_addr, e);
+        return None;
+    }
+    socket
+        .local_addr()
+        .ok()
+        .map(|addr| IpNetwork::from(addr.ip()))
+}
@@ -0,0 +1,327 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use pnet::ipnetwork::IpNetwork;
+use rand::Rng;
+use tokio::net::{lookup_host, UdpSocket};
+use tracing::debug;
+
+use crate::config::IP;
+
+/// STUN magic cookie (RFC 5389)
+static STUN_MAGIC_COOKIE: u32 = 0x2112A442;
+static STUN_DEFAULT_PORT: u16 = 3478;
+
+/// Get IP address from STUN servers (RFC 5389 Binding Request)
+///
+/// Servers are tried in order until one of them answers, separately for IPv4 and IPv6.
+pub async fn get_ip_via_stun(servers: &[String], timeout: Duration) -> Option<IP> {
+    let mut v4_addr: Option<IpAddr> = None;
+    let mut v6_addr: Option<IpAddr> = None;
+
+    for server in servers.iter() {
+        if v4_addr.is_some() && v6_addr.is_some() {
+            break;
+        }
+
+        let addrs = match resolve_stun_server(server).await {
+            Ok(addrs) => addrs,
+            Err(e) => {
+                debug!("Failed to resolve STUN server {}: {}", server, e);
+                continue;
+            }
+        };
+
+        for addr in addrs {
+            let slot = if addr.is_ipv4() {
+                &mut v4_addr
+            } else {
+                &mut v6_addr
+            };
+            if slot.is_some() {
+                continue;
+            }
+            match stun_binding_request(addr, timeout).await {
+                Ok(ip) => {
+                    debug!("STUN server {} ({}) reported {}", server, addr, ip);
+                    *slot = Some(ip);
+                }
+                Err(e) => debug!("STUN server {} ({}) failed: {}", server, addr, e),
+            }
+        }
+    }
+
+    if v4_addr.is_none() && v6_addr.is_none() {
+        None
+    } else {
+        Some(IP {
+            v4: v4_addr.map(IpNetwork::from),
+            v6: v6_addr.map(IpNetwork::from),
+        })
+    }
+}
+
+/// Resolve `host`, `host:port`, `IP` or `[IPv6]:port` to socket addresses
+async fn resolve_stun_server(server: &str) -> std::io::Result<Vec<SocketAddr>> {
+    if let Ok(addr) = server.parse::<SocketAddr>() {
+        return Ok(vec![addr]);
+    }
+    if let Ok(ip) = server.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, STUN_DEFAULT_PORT)]);
+    }
+    match server.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => Ok(lookup_host(server).await?.collect()),
+        _ => Ok(lookup_host((server, STUN_DEFAULT_PORT)).await?.collect()),
+    }
+}
+
+/// Send a Binding Request and read the mapped address from the response
+async fn stun_binding_request(server: SocketAddr, timeout: Duration) -> Result<IpAddr, String> {
+    let bind_addr = if server.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind_addr)
+        .await
+        .map_err(|e| e.to_string())?;
+    socket.connect(server).await.map_err(|e| e.to_string())?;
+
+    // Header: type(Binding Request), length, magic cookie, transaction id
+    let transaction_id: [u8; 12] = rand::thread_rng().gen();
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&0x0001u16.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&transaction_id);
+    socket.send(&request).await.map_err(|e| e.to_string())?;
+
+    let mut buffer = [0u8; 1024];
+    let size = tokio::time::timeout(timeout, socket.recv(&mut buffer))
+        .await
+        .map_err(|_| String::from("timed out"))?
+        .map_err(|e| e.to_string())?;
+
+    parse_stun_response(&buffer[..size], &transaction_id)
+}
+
+/// Parse a Binding Success Response, preferring XOR-MAPPED-ADDRESS over MAPPED-ADDRESS
+fn parse_stun_response(response: &[u8], transaction_id: &[u8; 12]) -> Result<IpAddr, String> {
+    if response.len() < 20 {
+        return Err(String::from("response too short"));
+    }
+    let message_type = u16::from_be_bytes([response[0], response[1]]);
+    if message_type != 0x0101 {
+        return Err(format!("unexpected message type {:#06x}", message_type));
+    }
+    if response[8..20] != transaction_id[..] {
+        return Err(String::from("transaction id mismatch"));
+    }
+
+    let length = u16::from_be_bytes([response[2], response[3]]) as usize;
+    let attributes = response
+        .get(20..20 + length)
+        .ok_or_else(|| String::from("truncated response"))?;
+
+    let mut mapped_address = None;
+    let mut offset = 0;
+    while offset + 4 <= attributes.len() {
+        let attribute_type = u16::from_be_bytes([attributes[offset], attributes[offset + 1]]);
+        let attribute_length =
+            u16::from_be_bytes([attributes[offset + 2], attributes[offset + 3]]) as usize;
+        let value = attributes
+            .get(offset + 4..offset + 4 + attribute_length)
+            .ok_or_else(|| String::from("truncated attribute"))?;
+
+        match attribute_type {
+            // XOR-MAPPED-ADDRESS (and its pre-RFC 5389 code point)
+            0x0020 | 0x8020 => return decode_stun_address(value, Some(transaction_id)),
+            // MAPPED-ADDRESS
+            0x0001 => mapped_address = Some(decode_stun_address(value, None)?),
+            _ => (),
+        }
+
+        // attributes are padded to a multiple of 4 bytes
+        offset += 4 + attribute_length.div_ceil(4) * 4;
+    }
+
+    mapped_address.ok_or_else(|| String::from("no mapped address in response"))
+}
+
+/// Decode a (XOR-)MAPPED-ADDRESS value, un-XORing it when a transaction id is given
+fn decode_stun_address(value: &[u8], transaction_id: Option<&[u8; 12]>) -> Result<IpAddr, String> {
+    if value.len() < 4 {
+        return Err(String::from("invalid address attribute"));
+    }
+
+    // XOR key: magic cookie followed by the transaction id
+    let mut key = [0u8; 16];
+    key[..4].copy_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    if let Some(transaction_id) = transaction_id {
+        key[4..].copy_from_slice(transaction_id);
+    }
+    let xor = |bytes: &[u8]| -> Vec<u8> {
+        match transaction_id {
+            Some(_) => bytes.iter().zip(key.iter()).map(|(b, k)| b ^ k).collect(),
+            None => bytes.to_vec(),
+        }
+    };
+
+    match (value[1], value.len()) {
+        (0x01, 8) => {
+            let octets = xor(&value[4..8]);
+            Ok(IpAddr::V4(Ipv4Addr::new(
+                octets[0], octets[1], octets[2], octets[3],
+            )))
+        }
+        (0x02, 20) => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&xor(&value[4..20]));
+            Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        (family, _) => Err(format!("unsupported address family {:#04x}", family)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transaction id of the RFC 5769 sample messages
+    const TRANSACTION_ID: [u8; 12] = [
+        0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86, 0xfa, 0x87, 0xdf, 0xae,
+    ];
+
+    /// RFC 5769 2.2 Sample IPv4 Response: 192.0.2.1 port 32853
+    const SAMPLE_V4: [u8; 80] = [
+        0x01, 0x01, 0x00, 0x3c, 0x21, 0x12, 0xa4, 0x42, 0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6,
+        0x86, 0xfa, 0x87, 0xdf, 0xae, 0x80, 0x22, 0x00, 0x0b, 0x74, 0x65, 0x73, 0x74, 0x20, 0x76,
+        0x65, 0x63, 0x74, 0x6f, 0x72, 0x20, 0x00, 0x20, 0x00, 0x08, 0x00, 0x01, 0xa1, 0x47, 0xe1,
+        0x12, 0xa6, 0x43, 0x00, 0x08, 0x00, 0x14, 0x2b, 0x91, 0xf5, 0x99, 0xfd, 0x9e, 0x90, 0xc3,
+        0x8c, 0x74, 0x89, 0xf9, 0x2a, 0xf9, 0xba, 0x53, 0xf0, 0x6b, 0xe7, 0xd7, 0x80, 0x28, 0x00,
+        0x04, 0xc0, 0x7d, 0x4c, 0x96,
+    ];
+
+    /// RFC 5769 2.3 Sample IPv6 Response: 2001:db8:1234:5678:11:2233:4455:6677 port 32853
+    const SAMPLE_V6: [u8; 92] = [
+        0x01, 0x01, 0x00, 0x48, 0x21, 0x12, 0xa4, 0x42, 0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6,
+        0x86, 0xfa, 0x87, 0xdf, 0xae, 0x80, 0x22, 0x00, 0x0b, 0x74, 0x65, 0x73, 0x74, 0x20, 0x76,
+        0x65, 0x63, 0x74, 0x6f, 0x72, 0x20, 0x00, 0x20, 0x00, 0x14, 0x00, 0x02, 0xa1, 0x47, 0x01,
+        0x13, 0xa9, 0xfa, 0xa5, 0xd3, 0xf1, 0x79, 0xbc, 0x25, 0xf4, 0xb5, 0xbe, 0xd2, 0xb9, 0xd9,
+        0x00, 0x08, 0x00, 0x14, 0xa3, 0x82, 0x95, 0x4e, 0x4b, 0xe6, 0x7b, 0xf1, 0x17, 0x84, 0xc9,
+        0x7c, 0x82, 0x92, 0xc2, 0x75, 0xbf, 0xe3, 0xed, 0x41, 0x80, 0x28, 0x00, 0x04, 0xc8, 0xfb,
+        0x0b, 0x4c,
+    ];
+
+    /// Binding Success Response with the given attributes
+    fn response(attributes: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (attribute_type, value) in attributes {
+            body.extend_from_slice(&attribute_type.to_be_bytes());
+            body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            body.extend_from_slice(value);
+            body.resize(body.len().div_ceil(4) * 4, 0);
+        }
+        let mut message = vec![0x01, 0x01];
+        message.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        message.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+        message.extend_from_slice(&TRANSACTION_ID);
+        message.extend(body);
+        message
+    }
+
+    #[test]
+    fn rfc5769_xor_mapped_address() {
+        assert_eq!(
+            parse_stun_response(&SAMPLE_V4, &TRANSACTION_ID),
+            Ok(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)))
+        );
+        assert_eq!(
+            parse_stun_response(&SAMPLE_V6, &TRANSACTION_ID),
+            Ok(IpAddr::V6(
+                "2001:db8:1234:5678:11:2233:4455:6677".parse().unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn mapped_address_fallback() {
+        // MAPPED-ADDRESS: reserved, family, port, address (not XORed)
+        let mapped_v4 = vec![0, 0x01, 0x80, 0x55, 203, 0, 113, 7];
+        assert_eq!(
+            parse_stun_response(&response(&[(0x0001, mapped_v4.clone())]), &TRANSACTION_ID),
+            Ok(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)))
+        );
+
+        let v6: Ipv6Addr = "2001:db8::7".parse().unwrap();
+        let mut mapped_v6 = vec![0, 0x02, 0x80, 0x55];
+        mapped_v6.extend_from_slice(&v6.octets());
+        assert_eq!(
+            parse_stun_response(&response(&[(0x0001, mapped_v6)]), &TRANSACTION_ID),
+            Ok(IpAddr::V6(v6))
+        );
+
+        // XOR-MAPPED-ADDRESS is preferred wherever it appears
+        let xor_mapped = SAMPLE_V4[40..48].to_vec();
+        for attributes in [
+            vec![(0x0001, mapped_v4.clone()), (0x0020, xor_mapped.clone())],
+            vec![(0x8020, xor_mapped.clone()), (0x0001, mapped_v4.clone())],
+        ] {
+            assert_eq!(
+                parse_stun_response(&response(&attributes), &TRANSACTION_ID),
+                Ok(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)))
+            );
+        }
+    }
+
+    #[test]
+    fn reject_invalid_responses() {
+        let mut other_id = TRANSACTION_ID;
+        other_id[11] ^= 1;
+        assert_eq!(
+            parse_stun_response(&SAMPLE_V4, &other_id),
+            Err(String::from("transaction id mismatch"))
+        );
+
+        // Binding Error Response
+        let mut error = SAMPLE_V4;
+        error[1] = 0x11;
+        assert!(parse_stun_response(&error, &TRANSACTION_ID).is_err());
+
+        // no mapped address
+        assert!(
+            parse_stun_response(&response(&[(0x8022, b"test".to_vec())]), &TRANSACTION_ID).is_err()
+        );
+        // unknown family or wrong length
+        assert!(parse_stun_response(
+            &response(&[(0x0020, vec![0, 0x03, 0, 0, 1, 2, 3, 4])]),
+            &TRANSACTION_ID
+        )
+        .is_err());
+        assert!(parse_stun_response(
+            &response(&[(0x0020, vec![0, 0x01, 0, 0, 1, 2])]),
+            &TRANSACTION_ID
+        )
+        .is_err());
+
+        // every truncation fails without panicking
+        for sample in [&SAMPLE_V4[..], &SAMPLE_V6[..]] {
+            for end in 0..sample.len() {
+                let mut truncated = sample[..end].to_vec();
+                assert!(
+                    parse_stun_response(&truncated, &TRANSACTION_ID).is_err(),
+                    "prefix {}",
+                    end
+                );
+                // also with the length field fixed up to the truncated size
+                if end >= 20 {
+                    let length = (end - 20) as u16;
+                    truncated[2..4].copy_from_slice(&length.to_be_bytes());
+                    let _ = parse_stun_response(&truncated, &TRANSACTION_ID);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::net::IpAddr;
+
+use tracing::warn;
+
+use crate::config::{select::AddressRange, MonitorRecord};
+
+/// 发布前的地址检查
+///
+/// 依次按以下规则检查：`deny` 中的地址拒绝发布；`allow` 中的地址直接发布；
+/// 未指定、回环、链路本地、组播、广播及文档示例地址拒绝发布；
+/// IPv4私有地址、运营商级NAT地址及IPv6唯一本地地址可以发布，但会给出警告
+pub struct AddressValidator {
+    allow: Vec<AddressRange>,
+    deny: Vec<AddressRange>,
+}
+
+impl AddressValidator {
+    pub fn new(record: &MonitorRecord) -> AddressValidator {
+        // 配置检查已确保规则合法
+        let parse = |ranges: &[String]| -> Vec<AddressRange> {
+            ranges
+                .iter()
+                .filter_map(|range| range.parse().ok())
+                .collect()
+        };
+        AddressValidator {
+            allow: parse(&record.allow),
+            deny: parse(&record.deny),
+        }
+    }
+
+    /// 检查地址能否发布到解析记录
+    ///
+    /// # Return
+    ///     Result<(), String>: 拒绝发布时返回原因
+    pub fn check(&self, record: &MonitorRecord, ip: IpAddr) -> Result<(), String> {
+        if let Some(range) = self.deny.iter().find(|range| range.contains(ip)) {
+            return Err(format!("{} is in the denied range {}", ip, range));
+        }
+        if self.allow.iter().any(|range| range.contains(ip)) {
+            return Ok(());
+        }
+
+        if let Some(reason) = Self::rejected(ip) {
+            return Err(format!("{} is {} address", ip, reason));
+        }
+        if let Some(reason) = Self::warned(ip) {
+            warn!(
+                "Record \"{}\" ({}) is going to use {}, which is {} address and not publicly routable",
+                record.hostname, record.record_type, ip, reason
+            );
+        }
+        Ok(())
+    }
+
+    /// 默认拒绝发布的地址
+    fn rejected(ip: IpAddr) -> Option<&'static str> {
+        if ip.is_unspecified() {
+            Some("an unspecified")
+        } else if ip.is_loopback() {
+            Some("a loopback")
+        } else if AddressRange::LinkLocal.contains(ip) {
+            Some("a link-local")
+        } else if ip.is_multicast() {
+            Some("a multicast")
+        } else if matches!(ip, IpAddr::V4(ip) if ip.is_broadcast()) {
+            Some("a broadcast")
+        } else if AddressRange::Documentation.contains(ip) {
+            Some("a documentation")
+        } else {
+            None
+        }
+    }
+
+    /// 可以发布但需给出警告的地址
+    fn warned(ip: IpAddr) -> Option<&'static str> {
+        match ip {
+            IpAddr::V4(v4) if v4.is_private() => Some("a private"),
+            IpAddr::V4(_) if AddressRange::Private.contains(ip) => Some("a carrier-grade NAT"),
+            IpAddr::V6(_) if AddressRange::Ula.contains(ip) => Some("a unique local"),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+
+    use super::*;
+
+    fn check(options: &str, ip: &str) -> Result<(), String> {
+        let config = Config::for_test(&format!(
+            "[[record]]\nrecord-type = \"A\"\nhostname = \"www\"\n{}",
+            options
+        ));
+        let record = &config.records[0];
+        AddressValidator::new(record).check(record, ip.parse().unwrap())
+    }
+
+    #[test]
+    fn reject_unusable_addresses() {
+        let cases = [
+            ("0.0.0.0", "unspecified"),
+            ("::", "unspecified"),
+            ("127.0.0.1", "loopback"),
+            ("::1", "loopback"),
+            ("169.254.10.1", "link-local"),
+            ("fe80::1", "link-local"),
+            ("febf::1", "link-local"),
+            ("224.0.0.251", "multicast"),
+            ("ff02::1", "multicast"),
+            ("255.255.255.255", "broadcast"),
+            ("192.0.2.1", "documentation"),
+            ("198.51.100.1", "documentation"),
+            ("203.0.113.1", "documentation"),
+            ("2001:db8::1", "documentation"),
+        ];
+        for (ip, reason) in cases {
+            let err = check("", ip).unwrap_err();
+            assert!(err.contains(reason), "{ip}: {err}");
+        }
+        for ip in [
+            "8.8.8.8",
+            "2400:3200::1",
+            "192.168.1.2",
+            "100.64.0.1",
+            "fd00::1",
+        ] {
+            assert_eq!(check("", ip), Ok(()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn deny_before_allow() {
+        // allow skips the default rules
+        assert_eq!(check("allow = [\"documentation\"]", "192.0.2.1"), Ok(()));
+        assert_eq!(check("allow = [\"127.0.0.0/8\"]", "127.0.0.1"), Ok(()));
+        assert!(check("allow = [\"127.0.0.0/8\"]", "169.254.0.1").is_err());
+        // deny applies to otherwise valid addresses
+        let err = check("deny = [\"8.8.0.0/16\"]", "8.8.8.8").unwrap_err();
+        assert!(err.contains("denied range 8.8.0.0/16"), "{err}");
+        assert_eq!(check("deny = [\"8.8.0.0/16\"]", "1.1.1.1"), Ok(()));
+        assert!(check("deny = [\"private\"]", "100.100.1.1").is_err());
+        assert!(check("deny = [\"ula\"]", "fd12:3456::1").is_err());
+        // deny takes precedence over allow
+        let options = "allow = [\"documentation\"]\ndeny = [\"192.0.2.0/25\"]";
+        assert!(check(options, "192.0.2.1").unwrap_err().contains("denied"));
+        assert_eq!(check(options, "192.0.2.200"), Ok(()));
+    }
+
+    #[test]
+    fn warn_about_non_routable_addresses() {
+        let cases = [
+            ("10.1.2.3", Some("a private")),
+            ("172.16.0.1", Some("a private")),
+            ("192.168.1.2", Some("a private")),
+            ("100.64.0.1", Some("a carrier-grade NAT")),
+            ("100.127.255.254", Some("a carrier-grade NAT")),
+            ("fd00::1", Some("a unique local")),
+            ("fc00::1", Some("a unique local")),
+            ("100.128.0.1", None),
+            ("172.32.0.1", None),
+            ("8.8.8.8", None),
+            ("2400:3200::1", None),
+        ];
+        for (ip, reason) in cases {
+            assert_eq!(
+                AddressValidator::warned(ip.parse().unwrap()),
+                reason,
+                "{ip}"
+            );
+        }
+    }
+}
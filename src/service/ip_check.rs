@@ -1,190 +1,612 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, io::Result, sync::Arc, time::Duration};
 
+use tokio::{sync::mpsc::UnboundedReceiver, task::JoinSet};
 use tracing::{debug, info, trace, warn};
 
 use crate::{
-    config::{record::Recorder, IP},
-    service::get_ip::{get_ip_via_nic, get_ip_via_socket},
+    config::{
+        history::{HistoryEntry, HistoryEvent, HistoryLog},
+        record::Recorder,
+        Check, Config, Propagation, Record,
+    },
+    service::get_ip::IpSources,
     GLOBAL_CONFIG,
 };
 
-use super::alidns::dns_operation::AliyunDnsOperate;
+use super::{
+    address_watch::AddressWatcher,
+    alidns::dns_operation::AliyunDnsOperate,
+    change_confirm::ChangeConfirmer,
+    control::ControlRequest,
+    drift_check::DriftChecker,
+    propagation::PropagationWaiter,
+    retry_queue::{is_transient, RetryQueue},
+};
 
 pub struct IpCheckService {
     check_interval: u64,
-    enable_recheck: bool,
     recheck_interval: u64,
+    reconcile_on_start: bool,
+    reconcile_interval: u64,
     recorder: Recorder,
     dns_operate: AliyunDnsOperate,
+    ip_sources: IpSources,
+    change_confirmer: ChangeConfirmer,
+    retry_queue: RetryQueue,
+    drift_checker: Option<DriftChecker>,
+    propagation_waiter: Option<Arc<PropagationWaiter>>,
+    /// 后台等待解析记录生效的任务，结果中每项为 (记录类型, 主机记录, 生效情况)
+    propagation_tasks: JoinSet<Vec<(String, String, Propagation)>>,
+    address_watcher: Option<AddressWatcher>,
+    history: HistoryLog,
+    /// 各记录最近一次检测到的地址的来源，键为 (记录类型, 主机记录)
+    target_sources: HashMap<(String, String), String>,
+    /// 当前生效的配置，重新加载时用于对比
+    config: Arc<Config>,
+    /// 配置重新加载的通知
+    config_receiver: tokio::sync::watch::Receiver<u64>,
+    /// 手动触发检查的请求
+    control_receiver: UnboundedReceiver<ControlRequest>,
+    /// 下一次检查时是否重新发布全部记录
+    republish: bool,
 }
 
 impl IpCheckService {
     pub fn new(
         check_interval: u64,
-        enable_recheck: bool,
         recheck_interval: u64,
+        reconcile_on_start: bool,
+        reconcile_interval: u64,
         recorder: Recorder,
+        control_receiver: UnboundedReceiver<ControlRequest>,
     ) -> IpCheckService {
+        let config = GLOBAL_CONFIG.get();
         IpCheckService {
             check_interval,
-            enable_recheck,
             recheck_interval,
+            reconcile_on_start,
+            reconcile_interval,
             recorder,
             dns_operate: AliyunDnsOperate::new(),
+            ip_sources: IpSources::new(config.clone()),
+            change_confirmer: ChangeConfirmer::new(&config),
+            retry_queue: RetryQueue::new(&config),
+            drift_checker: config.drift_check.enable.then(DriftChecker::new),
+            propagation_waiter: config
+                .propagation
+                .enable
+                .then(|| Arc::new(PropagationWaiter::new())),
+            propagation_tasks: JoinSet::new(),
+            address_watcher: Self::watch_address(&config.check),
+            history: HistoryLog::new(GLOBAL_CONFIG.dir(), &config.history),
+            target_sources: HashMap::new(),
+            config_receiver: GLOBAL_CONFIG.subscribe(),
+            config,
+            control_receiver,
+            republish: false,
         }
     }
 
+    /// 监听网卡地址变化，未启用或无法监听时返回 None
+    fn watch_address(check: &Check) -> Option<AddressWatcher> {
+        if !check.watch_address {
+            return None;
+        }
+        match AddressWatcher::new(Duration::from_secs(check.watch_debounce)) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!(
+                    "Failed to watch address changes, fall back to polling: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// 应用重新加载的配置
+    ///
+    /// 按新的记录列表及各项设置重建IP来源等，待确认的变化、重试队列及记录状态保留，
+    /// 其中已不再配置的记录将被移除
+    fn apply_config(&mut self) {
+        let config = GLOBAL_CONFIG.get();
+        let previous = std::mem::replace(&mut self.config, config.clone());
+        self.check_interval = config.check.check_interval;
+        self.recheck_interval = config.check.recheck_interval;
+        self.reconcile_interval = config.check.reconcile_interval;
+        self.dns_operate = AliyunDnsOperate::new();
+        self.ip_sources = IpSources::new(config.clone());
+        self.change_confirmer.reload(&config);
+        self.retry_queue.reload(&config);
+        self.drift_checker = config.drift_check.enable.then(DriftChecker::new);
+        self.propagation_waiter = config
+            .propagation
+            .enable
+            .then(|| Arc::new(PropagationWaiter::new()));
+        if config.check.watch_address != previous.check.watch_address
+            || config.check.watch_debounce != previous.check.watch_debounce
+        {
+            self.address_watcher = Self::watch_address(&config.check);
+        }
+        self.history = HistoryLog::new(GLOBAL_CONFIG.dir(), &config.history);
+        self.target_sources.retain(|(record_type, hostname), _| {
+            config
+                .records
+                .iter()
+                .any(|record| record.record_type == *record_type && record.hostname == *hostname)
+        });
+    }
+
     pub async fn start(&mut self, mut shutdown_receiver: tokio::sync::broadcast::Receiver<()>) {
         // Initialization
-        let mut need_recheck = self.enable_recheck;
         let mut record = self.recorder.get_record();
-        let mut dns_records: HashMap<String, Vec<(String, String)>> = HashMap::new();
-
-        // Collect DNS records
-        for dns_record in GLOBAL_CONFIG.1.records.iter() {
-            dns_records
-                .entry(dns_record.nic_name.clone().unwrap_or("".to_string()))
-                .or_insert(Vec::new())
-                .push((dns_record.record_type.clone(), dns_record.hostname.clone()));
-        }
+        let mut check_count: u64 = 0;
 
-        loop {
-            // Check IP
-            let mut ip_map: HashMap<String, IP>;
-            {
-                let ip_via_socket = get_ip_via_socket().await;
-                let ip_via_nic = get_ip_via_nic();
+        'check: loop {
+            // one configuration for the whole check, taken from the applied one rather than
+            // GLOBAL_CONFIG so that it matches ip_sources even if a reload is still pending
+            let config = self.config.clone();
 
-                if ip_via_socket.is_none() || ip_via_nic.is_empty() {
-                    warn!(
-                        "Something wrong happened when getting IPs, retry in {} seconds",
-                        self.recheck_interval
-                    );
-                    tokio::time::sleep(tokio::time::Duration::from_secs(self.recheck_interval))
-                        .await;
-                    continue;
+            // Check IP
+            let targets = self.detect_targets(&config).await;
+            if targets.is_empty() {
+                warn!(
+                    "Something wrong happened when getting IPs, retry in {} seconds",
+                    self.recheck_interval
+                );
+                tokio::select! {
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(self.recheck_interval)) => (),
+                    Ok(_) = self.config_receiver.changed() => self.apply_config(),
+                    Some(request) = self.control_receiver.recv() => {
+                        self.republish |= request == ControlRequest::Republish;
+                    }
+                    _ = shutdown_receiver.recv() => break 'check,
                 }
-
-                // collect IPs
-                ip_map = ip_via_nic
-                    .iter()
-                    .map(|(name, ip)| (name.clone(), ip.clone()))
-                    .collect();
-                ip_map.insert("".to_string(), ip_via_socket.unwrap());
+                continue;
             }
 
             // record check time
             record.last_check = chrono::Utc::now().timestamp();
             self.recorder.update_record(record.clone());
 
-            // check if IP changed
-            let changed_list = self.check_if_changed(&ip_map);
-            if changed_list.is_empty() {
-                trace!("IP not changed");
-            } else {
-                // IP changed, check if need to update
-                if !need_recheck {
-                    // update IP
-                    info!("IP changed, updating DNS records");
-
-                    let mut success_count = 0;
-                    let mut failed_count = 0;
-
-                    record.last_ip = ip_map.clone();
-                    record.last_update = record.last_check;
-                    self.recorder.update_record(record.clone());
-
-                    // update DNS records
-                    for nic_name in changed_list {
-                        let ips = ip_map.get(&nic_name).unwrap();
-                        let dns_records = dns_records.get(&nic_name);
-                        if dns_records.is_none() {
-                            continue;
-                        }
-
-                        for dns_record in dns_records.unwrap() {
-                            let (record_type, hostname) = dns_record;
-                            let ip = if record_type == "A" {
-                                ips.v4.clone()
-                            } else {
-                                ips.v6.clone()
-                            };
-
-                            if ip.is_none() {
-                                warn!("No IP address found for record \"{}\"", hostname);
-                                continue;
-                            }
-
-                            let ip = ip.unwrap().ip().to_string();
-                            debug!(
-                                "Updating DNS record for {}.{} to {}",
-                                hostname, GLOBAL_CONFIG.1.domain_name, ip
-                            );
-                            let result = self
-                                .dns_operate
-                                .update_dns_record(&ip, record_type, hostname)
-                                .await;
-
-                            if result.is_err() {
-                                failed_count += 1;
-                                warn!(
-                                    "Failed to update DNS record for {}.{}",
-                                    hostname, GLOBAL_CONFIG.1.domain_name
-                                );
-                            } else {
-                                success_count += 1;
-                                info!(
-                                    "DNS record for {}.{} updated to {}",
-                                    hostname, GLOBAL_CONFIG.1.domain_name, ip
-                                );
-                            }
-                        }
+            // reconcile with the live zone on start and every N checks
+            check_count += 1;
+            // republish all records when requested, instead of reconciling
+            let republish = std::mem::take(&mut self.republish);
+            let need_reconcile = !republish
+                && ((check_count == 1 && self.reconcile_on_start)
+                    || (self.reconcile_interval > 0
+                        && check_count.is_multiple_of(self.reconcile_interval)));
+            let mut reconciled = false;
+            let mut updated = Vec::new();
+            if need_reconcile {
+                match self.reconcile(&config, &mut record, &targets).await {
+                    Ok(reconciled_targets) => {
+                        updated = reconciled_targets;
+                        reconciled = true;
                     }
-
-                    if failed_count > 0 {
+                    Err(e) => {
                         warn!(
-                            "Update complete, Success:{}, Fail:{}",
-                            failed_count, success_count
+                            "Failed to reconcile with the live zone, fall back to record file: {}",
+                            e
                         );
-                    } else {
-                        info!("All DNS records updated successfully");
                     }
-                    need_recheck = self.enable_recheck;
-                } else {
-                    trace!("IP changed, recheck in {} seconds", self.recheck_interval);
-                    need_recheck = false;
+                }
+            }
+
+            // check if IP changed, drifted records whose address changed since the last
+            // publish are left by reconcile to the confirmation below
+            let changed_list = Self::check_if_changed(&config, &record, &targets);
+            if !reconciled {
+                // drop the retries whose desired address is out of date
+                self.retry_queue.sync(&targets, &changed_list);
+            }
+            // publish only the changes which have been confirmed
+            // records waiting for retry are left to the retry queue
+            let new_changes: Vec<(String, String, String)> = changed_list
+                .iter()
+                .filter(|(record_type, hostname, _)| {
+                    !self.retry_queue.contains(record_type, hostname)
+                })
+                .cloned()
+                .collect();
+            let confirmed_list = if republish {
+                // publishing all records at once, nothing is left to confirm
+                self.change_confirmer.clear();
+                changed_list.clone()
+            } else {
+                self.change_confirmer.confirm(&targets, &new_changes)
+            };
+            for (record_type, hostname, ip) in confirmed_list.iter() {
+                let mut entry =
+                    self.history_entry(&config, record_type, hostname, HistoryEvent::Detected);
+                entry.old_value = record
+                    .published(&config.domain_name, hostname, record_type)
+                    .map(String::from);
+                entry.new_value = Some(ip.clone());
+                self.history.append(entry);
+            }
+            if republish {
+                // records whose live value already matches are left untouched by update_dns_record
+                info!("Republishing all DNS records");
+                updated.extend(
+                    self.update_dns_records(&config, &mut record, &targets)
+                        .await,
+                );
+            } else if changed_list.is_empty() {
+                trace!("IP not changed");
+            } else if confirmed_list.is_empty() {
+                trace!("IP changed, waiting for confirmation or retry");
+            } else {
+                // update IP
+                info!("IP changed, updating DNS records");
+                updated.extend(
+                    self.update_dns_records(&config, &mut record, &confirmed_list)
+                        .await,
+                );
+            }
+
+            // retry the failed updates which are due
+            let due_list = if republish {
+                Vec::new()
+            } else {
+                self.retry_queue.due()
+            };
+            if !due_list.is_empty() {
+                info!("Retrying {} failed DNS record updates", due_list.len());
+                updated.extend(
+                    self.update_dns_records(&config, &mut record, &due_list)
+                        .await,
+                );
+            }
+
+            // save the state of the records, only the published values are remembered
+            // so that failed updates are not mistaken for done
+            if !updated.is_empty() {
+                record.last_update = record.last_check;
+            }
+            if reconciled || republish || !confirmed_list.is_empty() || !due_list.is_empty() {
+                self.recorder.update_record(record.clone());
+            }
+
+            // wait for the updated records to take effect in the background,
+            // the results are saved while waiting for the next check
+            if let Some(propagation_waiter) = &self.propagation_waiter {
+                if !updated.is_empty() {
+                    let propagation_waiter = propagation_waiter.clone();
+                    let targets = updated.clone();
+                    self.propagation_tasks.spawn(async move {
+                        let results = propagation_waiter.wait(&targets).await;
+                        targets
+                            .into_iter()
+                            .zip(results)
+                            .map(|((record_type, hostname, _), (_, propagation))| {
+                                (record_type, hostname, propagation)
+                            })
+                            .collect()
+                    });
+                }
+            }
+
+            // verify what the authoritative nameservers serve
+            if let Some(drift_checker) = &self.drift_checker {
+                if !updated.is_empty() {
                     tokio::select! {
-                        _ = tokio::time::sleep(tokio::time::Duration::from_secs(self.recheck_interval)) => continue,    // wait for recheck
-                        _ = shutdown_receiver.recv() => break,
+                        _ = tokio::time::sleep(tokio::time::Duration::from_secs(drift_checker.update_delay)) => (),
+                        _ = shutdown_receiver.recv() => break 'check,
                     }
+                    drift_checker.check(&updated).await;
+                } else if drift_checker.interval > 0
+                    && check_count.is_multiple_of(drift_checker.interval)
+                {
+                    // compare with the published values, changes still waiting for
+                    // confirmation or retry are not drift
+                    let domain_name = &config.domain_name;
+                    let published: Vec<(String, String, String)> = targets
+                        .iter()
+                        .filter_map(|(record_type, hostname, _)| {
+                            record
+                                .published(domain_name, hostname, record_type)
+                                .map(|value| {
+                                    (record_type.clone(), hostname.clone(), value.to_string())
+                                })
+                        })
+                        .collect();
+                    drift_checker.check(&published).await;
                 }
             }
 
-            // wait for next check
-            tokio::select! {
-                _ = tokio::time::sleep(tokio::time::Duration::from_secs(self.check_interval)) => (),
-                _ = shutdown_receiver.recv() => break,
+            // wait for next check, or check at once when the address changes
+            // recheck sooner while some changes are waiting for confirmation or retry
+            let mut check_interval = tokio::time::Duration::from_secs(self.check_interval);
+            if self.change_confirmer.has_pending() {
+                trace!(
+                    "Changes waiting for confirmation, recheck in {} seconds",
+                    self.recheck_interval
+                );
+                check_interval =
+                    check_interval.min(tokio::time::Duration::from_secs(self.recheck_interval));
+            }
+            if let Some(next_retry) = self.retry_queue.next_due() {
+                check_interval = check_interval.min(next_retry);
+            }
+            let next_check = tokio::time::Instant::now() + check_interval;
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep_until(next_check) => break,
+                    Some(result) = self.propagation_tasks.join_next(), if !self.propagation_tasks.is_empty() => {
+                        match result {
+                            Ok(results) => self.save_propagation(&config, &mut record, results),
+                            Err(e) => warn!("Failed to wait for DNS records to take effect: {}", e),
+                        }
+                    }
+                    _ = Self::address_changed(&mut self.address_watcher) => {
+                        info!("Network address changed, checking now");
+                        break;
+                    }
+                    Ok(_) = self.config_receiver.changed() => {
+                        self.apply_config();
+                        info!("Configuration reloaded, checking now");
+                        break;
+                    }
+                    Some(request) = self.control_receiver.recv() => {
+                        self.republish |= request == ControlRequest::Republish;
+                        break;
+                    }
+                    _ = shutdown_receiver.recv() => break 'check,
+                }
             }
         }
         drop(shutdown_receiver);
     }
 
-    fn check_if_changed(&self, ip: &HashMap<String, IP>) -> Vec<String> {
-        let mut changed_list = Vec::new();
-        let record = self.recorder.get_record();
+    /// 保存解析记录的生效情况，此后已再次更新的记录不保存
+    fn save_propagation(
+        &mut self,
+        config: &Config,
+        record: &mut Record,
+        results: Vec<(String, String, Propagation)>,
+    ) {
+        let domain_name = &config.domain_name;
+        for (record_type, hostname, propagation) in results {
+            if record.published(domain_name, &hostname, &record_type)
+                == Some(propagation.value.as_str())
+            {
+                record
+                    .state(domain_name, &hostname, &record_type)
+                    .propagation = Some(propagation);
+            } else {
+                debug!(
+                    "DNS record for {}.{} changed while waiting for it to take effect",
+                    hostname, domain_name
+                );
+            }
+        }
+        self.recorder.update_record(record.clone());
+    }
 
-        for (name, ips) in ip.iter() {
-            if !record.last_ip.contains_key(name) {
-                changed_list.push(name.clone());
-                continue;
+    /// 等待网卡地址变化，未启用监听时永不返回
+    async fn address_changed(address_watcher: &mut Option<AddressWatcher>) {
+        match address_watcher {
+            Some(watcher) => watcher.changed().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// 对照域名下解析记录的实际值进行校正，更新与检测到的IP不一致的记录
+    ///
+    /// 仅立即更新检测到的IP与上次发布的地址相同、实际值却被修改的记录；
+    /// 检测到的IP与上次发布的地址不同时属于地址变化，与普通检查一样需经过确认
+    ///
+    /// # Return
+    ///     Result<Vec<(String, String, String)>>: 成功更新的记录
+    async fn reconcile(
+        &mut self,
+        config: &Config,
+        record: &mut Record,
+        detected: &[(String, String, String)],
+    ) -> Result<Vec<(String, String, String)>> {
+        debug!("Reconciling DNS records with the live zone");
+        let live_records = self.dns_operate.get_all_dns_records().await?;
+
+        let mut targets = Vec::new();
+        for (record_type, hostname, ip) in detected.iter() {
+            match live_records
+                .iter()
+                .find(|live| live.rr == *hostname && live.record_type == *record_type)
+            {
+                Some(live) if live.value == *ip => {
+                    self.retry_queue.remove(record_type, hostname);
+                    let state = record.state(&config.domain_name, hostname, record_type);
+                    state.value = Some(ip.clone());
+                    state.record_id = Some(live.record_id.clone());
+                    trace!(
+                        "DNS record for {}.{} is in sync",
+                        hostname,
+                        config.domain_name
+                    );
+                }
+                Some(live) => {
+                    info!(
+                        "DNS record for {}.{} drifted: live value is {}, detected {}",
+                        hostname, config.domain_name, live.value, ip
+                    );
+                    let mut entry =
+                        self.history_entry(config, record_type, hostname, HistoryEvent::Drift);
+                    entry.old_value = Some(live.value.clone());
+                    entry.new_value = Some(ip.clone());
+                    entry.record_id = Some(live.record_id.clone());
+                    self.history.append(entry);
+                    let domain_name = &config.domain_name;
+                    if record.published(domain_name, hostname, record_type) == Some(ip.as_str()) {
+                        targets.push((record_type.clone(), hostname.clone(), ip.clone()));
+                    } else {
+                        debug!(
+                            "Address of {}.{} changed since the last publish, leaving it to confirmation",
+                            hostname, domain_name
+                        );
+                    }
+                }
+                None => {
+                    warn!(
+                        "No {} record found in the zone for {}.{}",
+                        record_type, hostname, config.domain_name
+                    );
+                }
             }
+        }
 
-            let last_ip = record.last_ip.get(name).unwrap();
-            if last_ip != ips {
-                changed_list.push(name.clone());
+        if targets.is_empty() {
+            info!("No DNS record to correct in the live zone");
+            Ok(targets)
+        } else {
+            Ok(self.update_dns_records(config, record, &targets).await)
+        }
+    }
+
+    /// 按各记录的IP来源检测其应指向的地址，每项为 (记录类型, 主机记录, IP)
+    ///
+    /// 各记录的地址来源保存于 `target_sources`
+    async fn detect_targets(&mut self, config: &Config) -> Vec<(String, String, String)> {
+        let mut cache = HashMap::new();
+        let mut targets = Vec::new();
+
+        for dns_record in config.records.iter() {
+            match self.ip_sources.detect(dns_record, &mut cache).await {
+                Some((ip, source)) => {
+                    let key = (dns_record.record_type.clone(), dns_record.hostname.clone());
+                    self.target_sources.insert(key.clone(), source.to_string());
+                    targets.push((key.0, key.1, ip.ip().to_string()));
+                }
+                None => warn!(
+                    "No IP address found for record \"{}\" ({})",
+                    dns_record.hostname, dns_record.record_type
+                ),
+            }
+        }
+        targets
+    }
+
+    /// 更新解析记录，targets 中每项为 (记录类型, 主机记录, IP)
+    ///
+    /// 网络错误时立即重试，仍然失败的记录加入重试队列，结果记录于各解析记录的状态中
+    ///
+    /// # Return
+    ///     Vec<(String, String, String)>: 成功更新的记录
+    async fn update_dns_records(
+        &mut self,
+        config: &Config,
+        record: &mut Record,
+        targets: &[(String, String, String)],
+    ) -> Vec<(String, String, String)> {
+        let mut updated = Vec::new();
+        let mut failed_count = 0;
+
+        for (record_type, hostname, ip) in targets {
+            debug!(
+                "Updating DNS record for {}.{} to {}",
+                hostname, config.domain_name, ip
+            );
+            // a timed out request may still have taken effect, the retry then reports
+            // DomainRecordDuplicate which update_dns_record treats as success
+            let mut immediate_retries = config.retry.immediate_retries;
+            let result = loop {
+                match self
+                    .dns_operate
+                    .update_dns_record(ip, record_type, hostname)
+                    .await
+                {
+                    Err(e) if immediate_retries > 0 && is_transient(&e) => {
+                        immediate_retries -= 1;
+                        debug!(
+                            "Failed to update DNS record for {}.{}, retry at once: {}",
+                            hostname, config.domain_name, e
+                        );
+                    }
+                    result => break result,
+                }
+            };
+
+            let mut entry = self.history_entry(config, record_type, hostname, HistoryEvent::Update);
+            entry.new_value = Some(ip.clone());
+
+            let now = chrono::Utc::now().timestamp();
+            let state = record.state(&config.domain_name, hostname, record_type);
+            entry.old_value = state.value.clone();
+            state.attempts += 1;
+            match result {
+                Ok(result) => {
+                    entry.request_id = Some(result.request_id);
+                    entry.record_id = Some(result.record_id.clone());
+                    state.value = Some(ip.clone());
+                    state.record_id = Some(result.record_id);
+                    state.last_success = Some(now);
+                    state.failures = 0;
+                    self.retry_queue.remove(record_type, hostname);
+                    updated.push((record_type.clone(), hostname.clone(), ip.clone()));
+                    info!(
+                        "DNS record for {}.{} updated to {}",
+                        hostname, config.domain_name, ip
+                    );
+                }
+                Err(e) => {
+                    entry.success = false;
+                    entry.error = Some(e.to_string());
+                    entry.record_id = state.record_id.clone();
+                    state.last_failure = Some(now);
+                    state.last_error = Some(e.to_string());
+                    state.failures += 1;
+                    failed_count += 1;
+                    warn!(
+                        "Failed to update DNS record for {}.{}: {}",
+                        hostname, config.domain_name, e
+                    );
+                    self.retry_queue.schedule(record_type, hostname, ip, &e);
+                }
             }
+            self.history.append(entry);
+        }
+
+        if failed_count > 0 {
+            warn!(
+                "Update complete, Success:{}, Fail:{}",
+                updated.len(),
+                failed_count
+            );
+        } else {
+            info!("All DNS records updated successfully");
         }
+        updated
+    }
+
+    /// 变更历史中的一条记录，已填写FQDN及地址来源
+    fn history_entry(
+        &self,
+        config: &Config,
+        record_type: &str,
+        hostname: &str,
+        event: HistoryEvent,
+    ) -> HistoryEntry {
+        let mut entry = HistoryEntry::new(&config.fqdn(hostname), record_type, event);
+        entry.source = self
+            .target_sources
+            .get(&(record_type.to_string(), hostname.to_string()))
+            .cloned();
+        entry
+    }
 
-        changed_list
+    /// 找出地址与已发布的记录值不同的解析记录
+    fn check_if_changed(
+        config: &Config,
+        record: &Record,
+        targets: &[(String, String, String)],
+    ) -> Vec<(String, String, String)> {
+        targets
+            .iter()
+            .filter(|(record_type, hostname, ip)| {
+                record.published(&config.domain_name, hostname, record_type) != Some(ip.as_str())
+            })
+            .cloned()
+            .collect()
     }
 }
@@ -1,3 +1,10 @@
-mod alidns;
+mod address_watch;
+pub mod alidns;
+mod change_confirm;
+pub mod config_watch;
+pub mod control;
+mod drift_check;
 mod get_ip;
 pub mod ip_check;
+mod propagation;
+mod retry_queue;
@@ -0,0 +1,156 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, info, warn};
+
+use crate::{config::Propagation, util::dns_client::resolve_server, GLOBAL_CONFIG};
+
+use super::drift_check::DriftChecker;
+
+/// 更新解析记录后，轮询权威DNS服务器（及公共DNS）直至新的记录值生效或超时
+pub struct PropagationWaiter {
+    checker: DriftChecker,
+    timeout: Duration,
+    poll_interval: Duration,
+    public_resolvers: Vec<String>,
+}
+
+impl PropagationWaiter {
+    pub fn new() -> PropagationWaiter {
+        let propagation = &GLOBAL_CONFIG.get().propagation;
+        PropagationWaiter {
+            checker: DriftChecker::new(),
+            timeout: Duration::from_secs(propagation.timeout),
+            poll_interval: Duration::from_secs(propagation.poll_interval),
+            public_resolvers: propagation.public_resolvers.clone(),
+        }
+    }
+
+    /// 等待解析记录生效，targets 中每项为 (记录类型, 主机记录, IP)
+    ///
+    /// # Return
+    ///     Vec<(String, Propagation)>: 各记录的生效情况，键为 `FQDN/记录类型`
+    pub async fn wait(&self, targets: &[(String, String, String)]) -> Vec<(String, Propagation)> {
+        let started = Instant::now();
+        let updated_at = chrono::Utc::now().timestamp();
+        let servers = self.servers().await;
+
+        let mut results: Vec<(String, Propagation)> = targets
+            .iter()
+            .map(|(record_type, hostname, ip)| {
+                (
+                    format!("{}/{}", GLOBAL_CONFIG.get().fqdn(hostname), record_type),
+                    Propagation {
+                        value: ip.clone(),
+                        updated_at,
+                        confirmed_at: None,
+                        latency_ms: None,
+                    },
+                )
+            })
+            .collect();
+
+        if servers.is_empty() {
+            warn!("Propagation check skipped: no nameserver to poll");
+            return results;
+        }
+
+        let mut pending: Vec<usize> = (0..targets.len()).collect();
+        loop {
+            let mut still_pending = Vec::new();
+            for index in pending {
+                let (record_type, hostname, ip) = &targets[index];
+                if self.is_served(&servers, hostname, record_type, ip).await {
+                    let latency = started.elapsed();
+                    let (key, propagation) = &mut results[index];
+                    propagation.confirmed_at = Some(chrono::Utc::now().timestamp());
+                    propagation.latency_ms = Some(latency.as_millis() as u64);
+                    info!(
+                        "{} is now served as {} by all answering nameservers, propagation took {:.1}s",
+                        key,
+                        ip,
+                        latency.as_secs_f64()
+                    );
+                } else {
+                    still_pending.push(index);
+                }
+            }
+            pending = still_pending;
+
+            if pending.is_empty() || started.elapsed() >= self.timeout {
+                break;
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+
+        for index in pending {
+            warn!(
+                "{} was not served as {} within {} seconds",
+                results[index].0,
+                targets[index].2,
+                self.timeout.as_secs()
+            );
+        }
+        results
+    }
+
+    /// 需要轮询的服务器：权威DNS服务器与配置的公共DNS
+    async fn servers(&self) -> Vec<SocketAddr> {
+        let mut servers = match self.checker.authoritative_servers().await {
+            Ok(servers) => servers,
+            Err(e) => {
+                warn!("Failed to find authoritative nameservers: {}", e);
+                Vec::new()
+            }
+        };
+        for resolver in self.public_resolvers.iter() {
+            match resolve_server(resolver).await {
+                Ok(addrs) => servers.extend(addrs),
+                Err(e) => warn!("Failed to resolve public resolver {}: {}", resolver, e),
+            }
+        }
+        servers
+    }
+
+    /// 检查作出应答的服务器是否均已返回新的记录值
+    ///
+    /// 查询失败的服务器不参与判断（如本机没有IPv6路由时的IPv6地址、暂时不可达的公共DNS），
+    /// 但至少需要一台服务器作出应答
+    async fn is_served(
+        &self,
+        servers: &[SocketAddr],
+        hostname: &str,
+        record_type: &str,
+        ip: &str,
+    ) -> bool {
+        let mut answered = 0;
+        for server in servers.iter() {
+            match self
+                .checker
+                .query_served(*server, hostname, record_type)
+                .await
+            {
+                Ok(served) if served.len() == 1 && served[0].to_string() == ip => answered += 1,
+                Ok(served) => {
+                    debug!(
+                        "{} still serves {:?} for {}",
+                        server,
+                        served,
+                        GLOBAL_CONFIG.get().fqdn(hostname)
+                    );
+                    return false;
+                }
+                Err(e) => debug!("Failed to query {}, ignored: {}", server, e),
+            }
+        }
+        if answered == 0 {
+            debug!(
+                "No nameserver answered for {}",
+                GLOBAL_CONFIG.get().fqdn(hostname)
+            );
+        }
+        answered > 0
+    }
+}
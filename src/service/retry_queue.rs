@@ -0,0 +1,314 @@
+/// 更新失败的解析记录的重试队列
+///     按指数退避加随机抖动安排重试，超过最大重试次数后放弃，待下次检查时重新开始
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+use tracing::{info, warn};
+
+use crate::{config::Config, GLOBAL_CONFIG};
+
+/// 等待重试的解析记录，记录应指向 `ip`（期望值），但尚未成功发布
+struct RetryEntry {
+    ip: String,
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+pub struct RetryQueue {
+    max_attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+    /// 键为 (记录类型, 主机记录)
+    entries: HashMap<(String, String), RetryEntry>,
+}
+
+impl RetryQueue {
+    pub fn new(config: &Config) -> RetryQueue {
+        let retry = &config.retry;
+        RetryQueue {
+            max_attempts: retry.max_attempts,
+            initial_delay: Duration::from_secs(retry.initial_delay),
+            max_delay: Duration::from_secs(retry.max_delay),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// 按新的配置更新重试策略，不再配置的记录移出队列
+    ///
+    /// 已安排的重试时间不变，新的策略自下一次失败起生效
+    pub fn reload(&mut self, config: &Config) {
+        self.max_attempts = config.retry.max_attempts;
+        self.initial_delay = Duration::from_secs(config.retry.initial_delay);
+        self.max_delay = Duration::from_secs(config.retry.max_delay);
+        self.entries.retain(|(record_type, hostname), _| {
+            config
+                .records
+                .iter()
+                .any(|record| record.record_type == *record_type && record.hostname == *hostname)
+        });
+    }
+
+    /// 记录更新失败，安排下一次重试
+    pub fn schedule(&mut self, record_type: &str, hostname: &str, ip: &str, error: &Error) {
+        let key = (record_type.to_string(), hostname.to_string());
+        // 期望值改变时重新计数
+        let attempts = match self.entries.get(&key) {
+            Some(entry) if entry.ip == ip => entry.attempts + 1,
+            _ => 1,
+        };
+
+        if attempts > self.max_attempts {
+            self.entries.remove(&key);
+            if self.max_attempts > 0 {
+                warn!(
+                    "Giving up updating {}.{} ({}) to {} after {} retries: {}",
+                    hostname,
+                    GLOBAL_CONFIG.get().domain_name,
+                    record_type,
+                    ip,
+                    self.max_attempts,
+                    error
+                );
+            }
+            return;
+        }
+
+        let delay = self.backoff(attempts);
+        info!(
+            "Retry updating {}.{} ({}) to {} in {} seconds (attempt {}/{})",
+            hostname,
+            GLOBAL_CONFIG.get().domain_name,
+            record_type,
+            ip,
+            delay.as_secs(),
+            attempts,
+            self.max_attempts
+        );
+        self.entries.insert(
+            key,
+            RetryEntry {
+                ip: ip.to_string(),
+                attempts,
+                next_attempt: Instant::now() + delay,
+            },
+        );
+    }
+
+    /// 记录已成功发布，移出队列
+    pub fn remove(&mut self, record_type: &str, hostname: &str) {
+        self.entries
+            .remove(&(record_type.to_string(), hostname.to_string()));
+    }
+
+    /// 按本轮检测结果清理队列：期望值已改变或已与上次发布的地址一致的记录不再重试
+    ///
+    /// # Arguments
+    ///     detected: 本轮检测到的全部地址，每项为 (记录类型, 主机记录, IP)
+    ///     changed: 其中地址与上次发布的地址不同的记录
+    pub fn sync(
+        &mut self,
+        detected: &[(String, String, String)],
+        changed: &[(String, String, String)],
+    ) {
+        self.entries.retain(|(record_type, hostname), entry| {
+            let detected_ip = detected
+                .iter()
+                .find(|(t, h, _)| t == record_type && h == hostname)
+                .map(|(_, _, ip)| ip);
+            match detected_ip {
+                // 本轮未检测到该记录的地址，保留
+                None => true,
+                Some(ip) => {
+                    ip == &entry.ip
+                        && changed
+                            .iter()
+                            .any(|(t, h, _)| t == record_type && h == hostname)
+                }
+            }
+        });
+    }
+
+    /// 记录是否在队列中等待重试
+    pub fn contains(&self, record_type: &str, hostname: &str) -> bool {
+        self.entries
+            .contains_key(&(record_type.to_string(), hostname.to_string()))
+    }
+
+    /// 已到重试时间的记录，每项为 (记录类型, 主机记录, IP)
+    pub fn due(&self) -> Vec<(String, String, String)> {
+        let now = Instant::now();
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.next_attempt <= now)
+            .map(|((record_type, hostname), entry)| {
+                (record_type.clone(), hostname.clone(), entry.ip.clone())
+            })
+            .collect()
+    }
+
+    /// 距离最近一次重试的时间，队列为空时返回 None
+    pub fn next_due(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.entries
+            .values()
+            .map(|entry| entry.next_attempt.saturating_duration_since(now))
+            .min()
+    }
+
+    /// 第 attempts 次重试前的等待时间：初始等待时间每次翻倍，不超过最长等待时间，
+    /// 再随机取其 50%~100%，避免多个实例同时重试
+    fn backoff(&self, attempts: u32) -> Duration {
+        let delay = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(attempts - 1))
+            .min(self.max_delay);
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/// 是否为暂时性的网络错误（超时、连接失败），此类错误立即重试
+pub fn is_transient(error: &Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::TimedOut
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(hostname: &str, ip: &str) -> (String, String, String) {
+        (String::from("A"), hostname.to_string(), ip.to_string())
+    }
+
+    fn retry_queue(max_attempts: u32, initial_delay: u64, max_delay: u64) -> RetryQueue {
+        let mut config = Config::for_test(
+            "[[record]]\nrecord-type = \"A\"\nhostname = \"www\"\n\
+             [[record]]\nrecord-type = \"A\"\nhostname = \"api\"",
+        );
+        config.retry.max_attempts = max_attempts;
+        config.retry.initial_delay = initial_delay;
+        config.retry.max_delay = max_delay;
+        RetryQueue::new(&config)
+    }
+
+    fn fail(queue: &mut RetryQueue, hostname: &str, ip: &str) {
+        queue.schedule("A", hostname, ip, &Error::other("failed"));
+    }
+
+    fn attempts(queue: &RetryQueue, hostname: &str) -> Option<u32> {
+        queue
+            .entries
+            .get(&(String::from("A"), hostname.to_string()))
+            .map(|entry| entry.attempts)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_delay_with_jitter() {
+        let queue = retry_queue(5, 10, 300);
+        for attempts in [1, 2, 3, 5, 6, 10, 40, u32::MAX] {
+            let exponent = (attempts - 1).min(31);
+            let base = Duration::from_secs(10)
+                .saturating_mul(2u32.saturating_pow(exponent))
+                .min(Duration::from_secs(300));
+            for _ in 0..100 {
+                let delay = queue.backoff(attempts);
+                assert!(
+                    delay >= base / 2 && delay <= base,
+                    "attempt {}: {:?} not in {:?}..={:?}",
+                    attempts,
+                    delay,
+                    base / 2,
+                    base
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn give_up_after_max_attempts() {
+        let mut queue = retry_queue(2, 10, 300);
+        fail(&mut queue, "www", "192.0.2.1");
+        fail(&mut queue, "www", "192.0.2.1");
+        assert_eq!(attempts(&queue, "www"), Some(2));
+        fail(&mut queue, "www", "192.0.2.1");
+        assert!(!queue.contains("A", "www"));
+
+        // 期望值改变时重新计数
+        fail(&mut queue, "www", "192.0.2.1");
+        fail(&mut queue, "www", "192.0.2.2");
+        assert_eq!(attempts(&queue, "www"), Some(1));
+
+        // 不重试
+        let mut queue = retry_queue(0, 10, 300);
+        fail(&mut queue, "www", "192.0.2.1");
+        assert!(!queue.contains("A", "www"));
+        assert_eq!(queue.next_due(), None);
+    }
+
+    #[test]
+    fn due_after_delay() {
+        let mut queue = retry_queue(3, 0, 0);
+        fail(&mut queue, "www", "192.0.2.1");
+        assert_eq!(queue.due(), vec![target("www", "192.0.2.1")]);
+        assert_eq!(queue.next_due(), Some(Duration::ZERO));
+
+        let mut queue = retry_queue(3, 60, 300);
+        fail(&mut queue, "www", "192.0.2.1");
+        assert!(queue.due().is_empty());
+        let next_due = queue.next_due().unwrap();
+        assert!(next_due > Duration::from_secs(29) && next_due <= Duration::from_secs(60));
+
+        queue.remove("A", "www");
+        assert_eq!(queue.next_due(), None);
+    }
+
+    #[test]
+    fn sync_drops_outdated_entries() {
+        let mut queue = retry_queue(3, 10, 300);
+        for hostname in ["www", "api", "mail", "vpn"] {
+            fail(&mut queue, hostname, "192.0.2.1");
+        }
+
+        let detected = vec![
+            // 期望值未变，仍需发布：保留
+            target("www", "192.0.2.1"),
+            // 期望值已改变：移除
+            target("api", "192.0.2.2"),
+            // 已与上次发布的地址一致：移除
+            target("mail", "192.0.2.1"),
+        ];
+        let changed = vec![detected[0].clone(), detected[1].clone()];
+        queue.sync(&detected, &changed);
+
+        assert!(queue.contains("A", "www"));
+        assert!(!queue.contains("A", "api"));
+        assert!(!queue.contains("A", "mail"));
+        // 本轮未检测到地址：保留
+        assert!(queue.contains("A", "vpn"));
+    }
+
+    #[test]
+    fn reload_drops_removed_records() {
+        let mut queue = retry_queue(3, 10, 300);
+        fail(&mut queue, "www", "192.0.2.1");
+        fail(&mut queue, "api", "192.0.2.1");
+
+        let mut config = Config::for_test("[[record]]\nrecord-type = \"A\"\nhostname = \"www\"");
+        config.retry.max_attempts = 1;
+        queue.reload(&config);
+        assert!(queue.contains("A", "www"));
+        assert!(!queue.contains("A", "api"));
+        // 新的最大重试次数自下一次失败起生效
+        fail(&mut queue, "www", "192.0.2.1");
+        assert!(!queue.contains("A", "www"));
+    }
+}
@@ -0,0 +1,526 @@
+/// 简易DNS客户端
+///     https://www.rfc-editor.org/rfc/rfc1035
+///     Note: 仅实现本程序所需的查询与记录类型，UDP响应被截断时自动改用TCP重试
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use rand::Rng;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{lookup_host, TcpStream, UdpSocket},
+};
+use tracing::trace;
+
+/// 未能从系统配置中获取DNS服务器时使用的默认服务器（阿里公共DNS）
+static DEFAULT_RESOLVER: &str = "223.5.5.5:53";
+static DNS_PORT: u16 = 53;
+static CLASS_IN: u16 = 1;
+
+/// 查询的记录类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryType {
+    A,
+    Ns,
+    Txt,
+    Aaaa,
+}
+
+impl QueryType {
+    fn code(&self) -> u16 {
+        match self {
+            QueryType::A => 1,
+            QueryType::Ns => 2,
+            QueryType::Txt => 16,
+            QueryType::Aaaa => 28,
+        }
+    }
+
+    /// 由解析记录类型（`A` / `AAAA`）得到查询类型
+    pub fn from_record_type(record_type: &str) -> Option<QueryType> {
+        match record_type {
+            "A" => Some(QueryType::A),
+            "AAAA" => Some(QueryType::Aaaa),
+            "NS" => Some(QueryType::Ns),
+            "TXT" => Some(QueryType::Txt),
+            _ => None,
+        }
+    }
+}
+
+/// 资源记录数据
+#[derive(Debug, Clone, PartialEq)]
+pub enum RData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Ns(String),
+    Txt(String),
+    Other(u16),
+}
+
+/// DNS响应
+#[derive(Debug, Clone)]
+pub struct DnsResponse {
+    pub rcode: u8,
+    pub truncated: bool,
+    pub answers: Vec<RData>,
+}
+
+impl DnsResponse {
+    /// 取出响应中的全部IP地址
+    pub fn addresses(&self) -> Vec<IpAddr> {
+        self.answers
+            .iter()
+            .filter_map(|answer| match answer {
+                RData::A(ip) => Some(IpAddr::V4(*ip)),
+                RData::Aaaa(ip) => Some(IpAddr::V6(*ip)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// 向指定服务器发起查询
+///
+/// # Arguments
+///     server: DNS服务器地址
+///     name: 查询的域名
+///     query_type: 查询的记录类型
+///     use_tcp: 是否直接使用TCP查询
+///     timeout: 单次查询的超时时间
+pub async fn query(
+    server: SocketAddr,
+    name: &str,
+    query_type: QueryType,
+    use_tcp: bool,
+    timeout: Duration,
+) -> Result<DnsResponse> {
+    let id: u16 = rand::thread_rng().gen();
+    let request = build_query(id, name, query_type)?;
+
+    let response = if use_tcp {
+        query_via_tcp(server, &request, timeout).await?
+    } else {
+        let response = query_via_udp(server, &request, timeout).await?;
+        let parsed = parse_response(id, &response)?;
+        if !parsed.truncated {
+            return Ok(parsed);
+        }
+        // 响应被截断，改用TCP重新查询
+        trace!("Response from {} truncated, retry via TCP", server);
+        query_via_tcp(server, &request, timeout).await?
+    };
+
+    parse_response(id, &response)
+}
+
+/// 将 `IP`、`IP:端口` 或主机名形式的服务器地址解析为Socket地址
+pub async fn resolve_server(server: &str) -> Result<Vec<SocketAddr>> {
+    if let Ok(addr) = server.parse::<SocketAddr>() {
+        return Ok(vec![addr]);
+    }
+    if let Ok(ip) = server.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, DNS_PORT)]);
+    }
+    let host = server.trim_end_matches('.');
+    Ok(lookup_host((host, DNS_PORT)).await?.collect())
+}
+
+/// 获取系统配置的DNS服务器（/etc/resolv.conf 中的第一个 nameserver）
+pub fn system_resolver() -> SocketAddr {
+    let resolver = fs::read_to_string("/etc/resolv.conf")
+        .ok()
+        .and_then(|content| {
+            content.lines().find_map(|line| {
+                let mut fields = line.split_whitespace();
+                match (fields.next(), fields.next()) {
+                    (Some("nameserver"), Some(ip)) => ip.parse::<IpAddr>().ok(),
+                    _ => None,
+                }
+            })
+        });
+
+    match resolver {
+        Some(ip) => SocketAddr::new(ip, DNS_PORT),
+        None => DEFAULT_RESOLVER.parse().unwrap(),
+    }
+}
+
+async fn query_via_udp(server: SocketAddr, request: &[u8], timeout: Duration) -> Result<Vec<u8>> {
+    let bind_addr = if server.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(server).await?;
+    socket.send(request).await?;
+
+    let mut buffer = vec![0u8; 4096];
+    let size = tokio::time::timeout(timeout, socket.recv(&mut buffer))
+        .await
+        .map_err(|_| timed_out(server))??;
+    buffer.truncate(size);
+    Ok(buffer)
+}
+
+async fn query_via_tcp(server: SocketAddr, request: &[u8], timeout: Duration) -> Result<Vec<u8>> {
+    let exchange = async {
+        let mut stream = TcpStream::connect(server).await?;
+        // TCP报文前需附加两字节的长度
+        let mut message = (request.len() as u16).to_be_bytes().to_vec();
+        message.extend_from_slice(request);
+        stream.write_all(&message).await?;
+
+        let length = stream.read_u16().await?;
+        let mut buffer = vec![0u8; length as usize];
+        stream.read_exact(&mut buffer).await?;
+        Ok::<Vec<u8>, Error>(buffer)
+    };
+
+    tokio::time::timeout(timeout, exchange)
+        .await
+        .map_err(|_| timed_out(server))?
+}
+
+/// 构造查询报文
+fn build_query(id: u16, name: &str, query_type: QueryType) -> Result<Vec<u8>> {
+    let mut message = Vec::with_capacity(512);
+    // Header: ID, Flags(RD), QDCOUNT=1, ANCOUNT, NSCOUNT, ARCOUNT
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(&0x0100u16.to_be_bytes());
+    message.extend_from_slice(&1u16.to_be_bytes());
+    message.extend_from_slice(&[0u8; 6]);
+
+    // Question: QNAME, QTYPE, QCLASS
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid domain name: {}", name),
+            ));
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&query_type.code().to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    Ok(message)
+}
+
+/// 解析响应报文
+fn parse_response(id: u16, message: &[u8]) -> Result<DnsResponse> {
+    if message.len() < 12 {
+        return Err(malformed("response too short"));
+    }
+    if read_u16(message, 0)? != id {
+        return Err(malformed("response id mismatch"));
+    }
+
+    let flags = read_u16(message, 2)?;
+    let question_count = read_u16(message, 4)?;
+    let answer_count = read_u16(message, 6)?;
+
+    let mut offset = 12;
+    // 跳过问题部分
+    for _ in 0..question_count {
+        offset = skip_name(message, offset)? + 4;
+    }
+
+    let mut answers = Vec::with_capacity(answer_count as usize);
+    for _ in 0..answer_count {
+        let next = skip_name(message, offset)?;
+        let record_type = read_u16(message, next)?;
+        let data_length = read_u16(message, next + 8)? as usize;
+        let data_offset = next + 10;
+        let data = message
+            .get(data_offset..data_offset + data_length)
+            .ok_or_else(|| malformed("record data out of range"))?;
+
+        let data = match record_type {
+            1 if data_length == 4 => RData::A(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+            28 if data_length == 16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(data);
+                RData::Aaaa(Ipv6Addr::from(octets))
+            }
+            2 => RData::Ns(read_name(message, data_offset)?.0),
+            16 => {
+                // TXT记录由若干个带长度前缀的字符串组成
+                let mut text = String::new();
+                let mut position = 0;
+                while position < data.len() {
+                    let length = data[position] as usize;
+                    let end = (position + 1 + length).min(data.len());
+                    text.push_str(&String::from_utf8_lossy(&data[position + 1..end]));
+                    position = end;
+                }
+                RData::Txt(text)
+            }
+            other => RData::Other(other),
+        };
+
+        answers.push(data);
+        offset = data_offset + data_length;
+    }
+
+    Ok(DnsResponse {
+        rcode: (flags & 0x000F) as u8,
+        truncated: flags & 0x0200 != 0,
+        answers,
+    })
+}
+
+/// 读取（可能被压缩的）域名，返回域名及其后的偏移量
+fn read_name(message: &[u8], offset: usize) -> Result<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut position = offset;
+    let mut next = None;
+    let mut jumps = 0;
+
+    loop {
+        let length = *message
+            .get(position)
+            .ok_or_else(|| malformed("name out of range"))? as usize;
+        if length & 0xC0 == 0xC0 {
+            // 压缩指针
+            let pointer = (read_u16(message, position)? & 0x3FFF) as usize;
+            if next.is_none() {
+                next = Some(position + 2);
+            }
+            jumps += 1;
+            if jumps > 32 {
+                return Err(malformed("too many compression pointers"));
+            }
+            position = pointer;
+        } else if length == 0 {
+            break;
+        } else {
+            let label = message
+                .get(position + 1..position + 1 + length)
+                .ok_or_else(|| malformed("label out of range"))?;
+            labels.push(String::from_utf8_lossy(label).to_string());
+            position += 1 + length;
+        }
+    }
+
+    Ok((labels.join("."), next.unwrap_or(position + 1)))
+}
+
+fn skip_name(message: &[u8], offset: usize) -> Result<usize> {
+    Ok(read_name(message, offset)?.1)
+}
+
+fn read_u16(message: &[u8], offset: usize) -> Result<u16> {
+    message
+        .get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| malformed("unexpected end of message"))
+}
+
+fn malformed(reason: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Malformed DNS response: {}", reason),
+    )
+}
+
+fn timed_out(server: SocketAddr) -> Error {
+    Error::new(
+        ErrorKind::TimedOut,
+        format!("DNS query to {} timed out", server),
+    )
+}
+
+/// 测试用的本地DNS服务器
+#[cfg(test)]
+pub mod stub {
+    use std::net::SocketAddr;
+
+    use tokio::net::UdpSocket;
+
+    /// 正常应答的标志位：QR、RD、RA
+    pub static FLAGS_OK: u16 = 0x8180;
+
+    /// 构造响应报文：复制查询的ID与问题部分，回答部分的域名为指向问题的压缩指针
+    ///
+    /// # Arguments
+    ///     answers: 每项为 (记录类型, 记录数据)
+    pub fn response(query: &[u8], flags: u16, answers: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut message = query[..2].to_vec();
+        message.extend_from_slice(&flags.to_be_bytes());
+        message.extend_from_slice(&1u16.to_be_bytes());
+        message.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        message.extend_from_slice(&[0u8; 4]);
+        message.extend_from_slice(&query[12..]);
+        for (record_type, data) in answers {
+            message.extend_from_slice(&0xC00Cu16.to_be_bytes());
+            message.extend_from_slice(&record_type.to_be_bytes());
+            message.extend_from_slice(&1u16.to_be_bytes());
+            message.extend_from_slice(&60u32.to_be_bytes());
+            message.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            message.extend_from_slice(data);
+        }
+        message
+    }
+
+    /// TXT记录数据
+    pub fn txt(strings: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for string in strings {
+            data.push(string.len() as u8);
+            data.extend_from_slice(string.as_bytes());
+        }
+        data
+    }
+
+    /// 在本地启动UDP DNS服务器，对任何查询均以 answers 应答，需在 tokio 运行时中调用
+    pub async fn serve_udp(answers: Vec<(u16, Vec<u8>)>) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 512];
+            while let Ok((size, peer)) = socket.recv_from(&mut buffer).await {
+                let message = response(&buffer[..size], FLAGS_OK, &answers);
+                let _ = socket.send_to(&message, peer).await;
+            }
+        });
+        addr
+    }
+
+    /// 在当前线程中运行异步测试
+    pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::stub::{response, txt, FLAGS_OK};
+    use super::*;
+
+    fn example_query(query_type: QueryType) -> Vec<u8> {
+        build_query(0x1234, "example.com", query_type).unwrap()
+    }
+
+    #[test]
+    fn parse_compressed_names() {
+        let query = example_query(QueryType::Ns);
+        // ns1 + 指向问题中 example.com 的指针
+        let mut ns1 = vec![3];
+        ns1.extend_from_slice(b"ns1");
+        ns1.extend_from_slice(&0xC00Cu16.to_be_bytes());
+        let message = response(&query, FLAGS_OK, &[(2, ns1), (2, vec![0xC0, 0x0C])]);
+
+        let parsed = parse_response(0x1234, &message).unwrap();
+        assert_eq!(parsed.rcode, 0);
+        assert!(!parsed.truncated);
+        assert_eq!(
+            parsed.answers,
+            vec![
+                RData::Ns(String::from("ns1.example.com")),
+                RData::Ns(String::from("example.com"))
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_addresses() {
+        let query = example_query(QueryType::Aaaa);
+        let ipv6: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let message = response(
+            &query,
+            FLAGS_OK,
+            &[
+                (28, ipv6.octets().to_vec()),
+                (1, vec![192, 0, 2, 1]),
+                (5, vec![0xC0, 0x0C]),
+            ],
+        );
+        let parsed = parse_response(0x1234, &message).unwrap();
+        assert_eq!(
+            parsed.addresses(),
+            vec![IpAddr::V6(ipv6), IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))]
+        );
+        assert_eq!(parsed.answers[2], RData::Other(5));
+    }
+
+    #[test]
+    fn parse_txt() {
+        let query = example_query(QueryType::Txt);
+        let message = response(
+            &query,
+            FLAGS_OK,
+            &[(16, txt(&["203.0.113.", "7"])), (16, txt(&[""]))],
+        );
+        let parsed = parse_response(0x1234, &message).unwrap();
+        assert_eq!(
+            parsed.answers,
+            vec![
+                RData::Txt(String::from("203.0.113.7")),
+                RData::Txt(String::new())
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_truncated_flag() {
+        let query = example_query(QueryType::A);
+        let message = response(&query, FLAGS_OK | 0x0200, &[]);
+        assert!(parse_response(0x1234, &message).unwrap().truncated);
+    }
+
+    #[test]
+    fn reject_malformed() {
+        let query = example_query(QueryType::A);
+        let message = response(&query, FLAGS_OK, &[(1, vec![192, 0, 2, 1])]);
+
+        // 任意位置截断的报文均应返回错误
+        for length in 0..message.len() {
+            assert!(parse_response(0x1234, &message[..length]).is_err());
+        }
+        // ID不一致
+        assert!(parse_response(0x4321, &message).is_err());
+
+        // 指向自身的压缩指针
+        let mut looped = message.clone();
+        let name = looped.len() - 16;
+        looped[name..name + 2].copy_from_slice(&(name as u16 | 0xC000).to_be_bytes());
+        assert!(parse_response(0x1234, &looped).is_err());
+
+        // 记录数据长度超出报文
+        let mut overflow = message.clone();
+        let length = overflow.len() - 6;
+        overflow[length..length + 2].copy_from_slice(&0xFFFFu16.to_be_bytes());
+        assert!(parse_response(0x1234, &overflow).is_err());
+    }
+
+    #[test]
+    fn query_stub_server() {
+        stub::block_on(async {
+            let server = stub::serve_udp(vec![(1, vec![198, 51, 100, 9])]).await;
+            let response = query(
+                server,
+                "myip.example.com",
+                QueryType::A,
+                false,
+                Duration::from_secs(2),
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                response.addresses(),
+                vec![IpAddr::V4(Ipv4Addr::new(198, 51, 100, 9))]
+            );
+        });
+    }
+}
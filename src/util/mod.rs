@@ -1,4 +1,8 @@
+pub mod dns_client;
 pub mod log_collector;
+#[cfg(target_os = "linux")]
+pub mod netlink;
+pub mod route;
 
 /// 生成一个32位随机字符串，由小写字母和数字组成
 pub fn random_signature_nonce() -> String {
@@ -12,3 +16,40 @@ pub fn random_signature_nonce() -> String {
         .collect();
     nonce
 }
+
+/// 测试用的临时目录，离开作用域时连同其中的文件一并删除
+#[cfg(test)]
+pub struct TempDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "aliyun-ddns-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+
+    pub fn to_str(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
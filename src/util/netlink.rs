@@ -0,0 +1,579 @@
+/// 简易rtnetlink客户端（仅Linux）
+///     https://man7.org/linux/man-pages/man7/rtnetlink.7.html
+///     Note: 仅实现本程序所需的消息类型与属性
+use std::{
+    io::{Error, ErrorKind, Result},
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+
+static NLMSG_HEADER_LEN: usize = 16;
+static NLMSG_ERROR: u16 = 2;
+static NLMSG_DONE: u16 = 3;
+static NLM_F_REQUEST: u16 = 0x01;
+static NLM_F_DUMP: u16 = 0x300;
+
+pub static RTM_NEWLINK: u16 = 16;
+pub static RTM_DELLINK: u16 = 17;
+pub static RTM_NEWADDR: u16 = 20;
+pub static RTM_DELADDR: u16 = 21;
+static RTM_GETADDR: u16 = 22;
+static RTM_NEWROUTE: u16 = 24;
+static RTM_GETROUTE: u16 = 26;
+
+/// 多播组：网卡状态变化、IPv4地址变化、IPv6地址变化
+pub static RTMGRP_LINK: u32 = 0x01;
+pub static RTMGRP_IPV4_IFADDR: u32 = 0x10;
+pub static RTMGRP_IPV6_IFADDR: u32 = 0x100;
+
+// linux/if_addr.h
+static IFA_ADDRESS: u16 = 1;
+static IFA_LOCAL: u16 = 2;
+static IFA_CACHEINFO: u16 = 6;
+static IFA_FLAGS: u16 = 8;
+
+// linux/rtnetlink.h
+static RTA_OIF: u16 = 4;
+static RTA_GATEWAY: u16 = 5;
+static RTA_PRIORITY: u16 = 6;
+static RTA_PREFSRC: u16 = 7;
+static RTA_TABLE: u16 = 15;
+static RT_TABLE_MAIN: u32 = 254;
+static RTN_UNICAST: u8 = 1;
+
+/// IPv4为从地址（secondary），IPv6为临时地址（temporary），二者取值相同
+pub static IFA_F_SECONDARY: u32 = 0x01;
+pub static IFA_F_TEMPORARY: u32 = 0x01;
+pub static IFA_F_DADFAILED: u32 = 0x08;
+pub static IFA_F_DEPRECATED: u32 = 0x20;
+pub static IFA_F_TENTATIVE: u32 = 0x40;
+pub static IFA_F_MANAGETEMPADDR: u32 = 0x100;
+
+/// 生存期为无限时的取值
+pub static INFINITY_LIFE_TIME: u32 = 0xFFFF_FFFF;
+
+/// 网卡上的地址
+#[derive(Debug, Clone)]
+pub struct InterfaceAddress {
+    /// 网卡序号
+    pub index: u32,
+    pub address: IpAddr,
+    pub prefix_len: u8,
+    /// IFA_F_* 标志
+    pub flags: u32,
+    /// 首选生存期（单位：秒）
+    pub preferred_lifetime: u32,
+    /// 有效生存期（单位：秒）
+    pub valid_lifetime: u32,
+}
+
+/// 默认路由
+#[derive(Debug, Clone)]
+pub struct Route {
+    /// 出口网卡序号
+    pub index: u32,
+    pub gateway: Option<IpAddr>,
+    /// 首选源地址（RTA_PREFSRC）
+    pub preferred_source: Option<IpAddr>,
+    pub priority: u32,
+}
+
+/// NETLINK_ROUTE 套接字
+pub struct NetlinkSocket {
+    fd: OwnedFd,
+    sequence: u32,
+}
+
+impl NetlinkSocket {
+    /// 打开套接字
+    ///
+    /// # Arguments
+    ///     groups: 订阅的多播组（RTMGRP_*），仅发送请求时为0
+    pub fn open(groups: u32) -> Result<NetlinkSocket> {
+        // SAFETY: 参数均为常量，返回值在下方检查
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        // SAFETY: fd 为刚创建的有效描述符，由 OwnedFd 负责关闭
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: sockaddr_nl 全零为合法值
+        let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        address.nl_groups = groups;
+        // SAFETY: address 在调用期间有效，长度与类型一致
+        let result = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        Ok(NetlinkSocket { fd, sequence: 0 })
+    }
+
+    /// 设置为非阻塞模式
+    pub fn set_nonblocking(&self) -> Result<()> {
+        // SAFETY: fd 有效，F_GETFL / F_SETFL 不涉及指针
+        let result = unsafe {
+            let flags = libc::fcntl(self.fd.as_raw_fd(), libc::F_GETFL);
+            libc::fcntl(self.fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK)
+        };
+        if result < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// 发送请求并收集全部响应消息，直至 NLMSG_DONE
+    ///
+    /// # Return
+    ///     Vec<(u16, Vec<u8>)>: 各消息的类型与内容（不含消息头）
+    pub fn dump(&mut self, message_type: u16, payload: &[u8]) -> Result<Vec<(u16, Vec<u8>)>> {
+        self.sequence = self.sequence.wrapping_add(1);
+        let mut request = Vec::with_capacity(NLMSG_HEADER_LEN + payload.len());
+        request.extend_from_slice(&((NLMSG_HEADER_LEN + payload.len()) as u32).to_ne_bytes());
+        request.extend_from_slice(&message_type.to_ne_bytes());
+        request.extend_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+        request.extend_from_slice(&self.sequence.to_ne_bytes());
+        request.extend_from_slice(&0u32.to_ne_bytes());
+        request.extend_from_slice(payload);
+
+        // SAFETY: request 在调用期间有效
+        let sent = unsafe {
+            libc::send(
+                self.fd.as_raw_fd(),
+                request.as_ptr() as *const libc::c_void,
+                request.len(),
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let mut messages = Vec::new();
+        loop {
+            let buffer = self.recv()?;
+            for (message_type, sequence, body) in parse_messages(&buffer)? {
+                if sequence != self.sequence {
+                    continue;
+                }
+                if message_type == NLMSG_DONE {
+                    return Ok(messages);
+                }
+                if message_type == NLMSG_ERROR {
+                    let code = body
+                        .get(0..4)
+                        .map(|bytes| i32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                        .unwrap_or(0);
+                    if code != 0 {
+                        return Err(Error::from_raw_os_error(-code));
+                    }
+                    return Ok(messages);
+                }
+                messages.push((message_type, body.to_vec()));
+            }
+        }
+    }
+
+    /// 接收一个数据报，非阻塞模式下无数据时返回 WouldBlock
+    pub fn recv(&self) -> Result<Vec<u8>> {
+        let mut buffer = vec![0u8; 32 * 1024];
+        // SAFETY: buffer 在调用期间有效，长度与指针一致
+        let size = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+                0,
+            )
+        };
+        if size < 0 {
+            return Err(Error::last_os_error());
+        }
+        buffer.truncate(size as usize);
+        Ok(buffer)
+    }
+}
+
+impl AsRawFd for NetlinkSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// 获取系统中全部网卡的地址
+pub fn get_addresses() -> Result<Vec<InterfaceAddress>> {
+    let mut socket = NetlinkSocket::open(0)?;
+    // ifaddrmsg: family, prefixlen, flags, scope, index
+    let request = [libc::AF_UNSPEC as u8, 0, 0, 0, 0, 0, 0, 0];
+
+    Ok(socket
+        .dump(RTM_GETADDR, &request)?
+        .iter()
+        .filter(|(message_type, _)| *message_type == RTM_NEWADDR)
+        .filter_map(|(_, body)| parse_address(body))
+        .collect())
+}
+
+/// 获取主路由表中的默认路由，按 priority 升序排列
+///
+/// # Arguments
+///     ipv6: 获取IPv6（否则为IPv4）默认路由
+pub fn get_default_routes(ipv6: bool) -> Result<Vec<Route>> {
+    let family = if ipv6 { libc::AF_INET6 } else { libc::AF_INET };
+    let mut socket = NetlinkSocket::open(0)?;
+    // rtmsg: family, dst_len, src_len, tos, table, protocol, scope, type, flags
+    let mut request = [0u8; 12];
+    request[0] = family as u8;
+
+    let mut routes: Vec<Route> = socket
+        .dump(RTM_GETROUTE, &request)?
+        .iter()
+        .filter(|(message_type, _)| *message_type == RTM_NEWROUTE)
+        .filter_map(|(_, body)| parse_default_route(family, body))
+        .collect();
+    routes.sort_by_key(|route| route.priority);
+    Ok(routes)
+}
+
+/// 解析 RTM_NEWROUTE 消息，不是主路由表中的单播默认路由时返回 None
+fn parse_default_route(family: i32, body: &[u8]) -> Option<Route> {
+    if body.len() < 12 || body[0] as i32 != family || body[1] != 0 || body[7] != RTN_UNICAST {
+        return None;
+    }
+
+    let mut table = body[4] as u32;
+    let mut index = None;
+    let mut gateway = None;
+    let mut preferred_source = None;
+    let mut priority = 0;
+    for (attribute_type, data) in parse_attributes(&body[12..]) {
+        match attribute_type {
+            t if t == RTA_TABLE && data.len() >= 4 => table = read_u32(data),
+            t if t == RTA_OIF && data.len() >= 4 => index = Some(read_u32(data)),
+            t if t == RTA_PRIORITY && data.len() >= 4 => priority = read_u32(data),
+            t if t == RTA_GATEWAY => gateway = parse_ip(family, data),
+            t if t == RTA_PREFSRC => preferred_source = parse_ip(family, data),
+            _ => (),
+        }
+    }
+    if table != RT_TABLE_MAIN {
+        return None;
+    }
+
+    // 多路径路由没有 RTA_OIF，此处不处理
+    Some(Route {
+        index: index?,
+        gateway,
+        preferred_source,
+        priority,
+    })
+}
+
+/// 解析 RTM_NEWADDR / RTM_DELADDR 消息
+pub fn parse_address(body: &[u8]) -> Option<InterfaceAddress> {
+    if body.len() < 8 {
+        return None;
+    }
+    let family = body[0] as i32;
+    let prefix_len = body[1];
+    let mut flags = body[2] as u32;
+    let index = u32::from_ne_bytes([body[4], body[5], body[6], body[7]]);
+
+    let mut address = None;
+    let mut local = None;
+    let mut preferred_lifetime = INFINITY_LIFE_TIME;
+    let mut valid_lifetime = INFINITY_LIFE_TIME;
+    for (attribute_type, data) in parse_attributes(&body[8..]) {
+        match attribute_type {
+            t if t == IFA_ADDRESS => address = parse_ip(family, data),
+            t if t == IFA_LOCAL => local = parse_ip(family, data),
+            // 完整的32位标志，ifaddrmsg 中只有低8位
+            t if t == IFA_FLAGS && data.len() >= 4 => {
+                flags = u32::from_ne_bytes([data[0], data[1], data[2], data[3]])
+            }
+            // ifa_cacheinfo: ifa_prefered, ifa_valid, cstamp, tstamp
+            t if t == IFA_CACHEINFO && data.len() >= 8 => {
+                preferred_lifetime = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]);
+                valid_lifetime = u32::from_ne_bytes([data[4], data[5], data[6], data[7]]);
+            }
+            _ => (),
+        }
+    }
+
+    // 点对点链路上 IFA_ADDRESS 为对端地址，IFA_LOCAL 才是本机地址
+    Some(InterfaceAddress {
+        index,
+        address: local.or(address)?,
+        prefix_len,
+        flags,
+        preferred_lifetime,
+        valid_lifetime,
+    })
+}
+
+/// 将数据报拆分为消息，每项为 (类型, 序号, 内容)
+pub fn parse_messages(buffer: &[u8]) -> Result<Vec<(u16, u32, &[u8])>> {
+    let mut messages = Vec::new();
+    let mut offset = 0;
+    while offset + NLMSG_HEADER_LEN <= buffer.len() {
+        let header = &buffer[offset..offset + NLMSG_HEADER_LEN];
+        let length = u32::from_ne_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let message_type = u16::from_ne_bytes([header[4], header[5]]);
+        let sequence = u32::from_ne_bytes([header[8], header[9], header[10], header[11]]);
+        if length < NLMSG_HEADER_LEN || offset + length > buffer.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Malformed netlink message",
+            ));
+        }
+        messages.push((
+            message_type,
+            sequence,
+            &buffer[offset + NLMSG_HEADER_LEN..offset + length],
+        ));
+        offset += align(length);
+    }
+    // 剩余不足一个消息头的数据
+    if offset < buffer.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Truncated netlink message",
+        ));
+    }
+    Ok(messages)
+}
+
+/// 解析属性，每项为 (类型, 内容)
+fn parse_attributes(mut buffer: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut attributes = Vec::new();
+    while buffer.len() >= 4 {
+        let length = u16::from_ne_bytes([buffer[0], buffer[1]]) as usize;
+        // 最高两位为 NLA_F_NESTED / NLA_F_NET_BYTEORDER
+        let attribute_type = u16::from_ne_bytes([buffer[2], buffer[3]]) & 0x3FFF;
+        if length < 4 || length > buffer.len() {
+            break;
+        }
+        attributes.push((attribute_type, &buffer[4..length]));
+        buffer = &buffer[align(length).min(buffer.len())..];
+    }
+    attributes
+}
+
+fn parse_ip(family: i32, data: &[u8]) -> Option<IpAddr> {
+    if family == libc::AF_INET && data.len() >= 4 {
+        Some(IpAddr::V4(Ipv4Addr::new(
+            data[0], data[1], data[2], data[3],
+        )))
+    } else if family == libc::AF_INET6 && data.len() >= 16 {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&data[..16]);
+        Some(IpAddr::V6(Ipv6Addr::from(octets)))
+    } else {
+        None
+    }
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_ne_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn align(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+/// 构造测试用的消息
+#[cfg(test)]
+pub mod fixture {
+    /// 属性：长度、类型、内容，并对齐到4字节
+    pub fn attribute(attribute_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut attribute = Vec::new();
+        attribute.extend_from_slice(&((4 + data.len()) as u16).to_ne_bytes());
+        attribute.extend_from_slice(&attribute_type.to_ne_bytes());
+        attribute.extend_from_slice(data);
+        attribute.resize(super::align(attribute.len()), 0);
+        attribute
+    }
+
+    /// RTM_NEWADDR / RTM_DELADDR 的内容：ifaddrmsg 与属性
+    pub fn address(family: i32, prefix_len: u8, index: u32, attributes: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = vec![family as u8, prefix_len, 0, 0];
+        body.extend_from_slice(&index.to_ne_bytes());
+        for attribute in attributes {
+            body.extend_from_slice(attribute);
+        }
+        body
+    }
+
+    /// 带消息头的消息，并对齐到4字节
+    pub fn message(message_type: u16, sequence: u32, body: &[u8]) -> Vec<u8> {
+        let mut message = Vec::new();
+        message.extend_from_slice(&((super::NLMSG_HEADER_LEN + body.len()) as u32).to_ne_bytes());
+        message.extend_from_slice(&message_type.to_ne_bytes());
+        message.extend_from_slice(&0u16.to_ne_bytes());
+        message.extend_from_slice(&sequence.to_ne_bytes());
+        message.extend_from_slice(&0u32.to_ne_bytes());
+        message.extend_from_slice(body);
+        message.resize(super::align(message.len()), 0);
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixture::{address, attribute, message};
+    use super::*;
+
+    fn cache_info(preferred: u32, valid: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [preferred, valid, 100, 200] {
+            data.extend_from_slice(&value.to_ne_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn parse_new_and_deleted_addresses() {
+        let v4 = address(
+            libc::AF_INET,
+            24,
+            2,
+            &[
+                attribute(IFA_ADDRESS, &[192, 0, 2, 1]),
+                attribute(IFA_FLAGS, &IFA_F_SECONDARY.to_ne_bytes()),
+            ],
+        );
+        let v6_address: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let v6 = address(
+            libc::AF_INET6,
+            64,
+            3,
+            &[attribute(IFA_ADDRESS, &v6_address.octets())],
+        );
+        let mut buffer = message(RTM_NEWADDR, 7, &v4);
+        buffer.extend(message(RTM_DELADDR, 8, &v6));
+
+        let messages = parse_messages(&buffer).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!((messages[0].0, messages[0].1), (RTM_NEWADDR, 7));
+        assert_eq!((messages[1].0, messages[1].1), (RTM_DELADDR, 8));
+
+        let first = parse_address(messages[0].2).unwrap();
+        assert_eq!(first.address, IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!((first.index, first.prefix_len), (2, 24));
+        assert_eq!(first.flags, IFA_F_SECONDARY);
+        assert_eq!(first.preferred_lifetime, INFINITY_LIFE_TIME);
+        assert_eq!(first.valid_lifetime, INFINITY_LIFE_TIME);
+
+        let second = parse_address(messages[1].2).unwrap();
+        assert_eq!(second.address, IpAddr::V6(v6_address));
+        assert_eq!((second.index, second.prefix_len), (3, 64));
+    }
+
+    #[test]
+    fn parse_cache_info() {
+        let body = address(
+            libc::AF_INET6,
+            64,
+            2,
+            &[
+                attribute(
+                    IFA_ADDRESS,
+                    &"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets(),
+                ),
+                attribute(IFA_CACHEINFO, &cache_info(1800, 3600)),
+            ],
+        );
+        let parsed = parse_address(&body).unwrap();
+        assert_eq!(parsed.preferred_lifetime, 1800);
+        assert_eq!(parsed.valid_lifetime, 3600);
+
+        // 过短的 IFA_CACHEINFO 被忽略
+        let body = address(
+            libc::AF_INET,
+            32,
+            2,
+            &[
+                attribute(IFA_ADDRESS, &[192, 0, 2, 2]),
+                attribute(IFA_CACHEINFO, &[1, 2, 3]),
+            ],
+        );
+        let parsed = parse_address(&body).unwrap();
+        assert_eq!(parsed.preferred_lifetime, INFINITY_LIFE_TIME);
+        assert_eq!(parsed.valid_lifetime, INFINITY_LIFE_TIME);
+    }
+
+    #[test]
+    fn local_address_preferred_over_peer() {
+        let body = address(
+            libc::AF_INET,
+            32,
+            5,
+            &[
+                attribute(IFA_ADDRESS, &[198, 51, 100, 1]),
+                attribute(IFA_LOCAL, &[203, 0, 113, 9]),
+            ],
+        );
+        assert_eq!(
+            parse_address(&body).unwrap().address,
+            IpAddr::V4(Ipv4Addr::new(203, 0, 113, 9))
+        );
+    }
+
+    #[test]
+    fn reject_truncated_messages() {
+        let body = address(
+            libc::AF_INET,
+            24,
+            2,
+            &[
+                attribute(IFA_ADDRESS, &[192, 0, 2, 1]),
+                attribute(IFA_CACHEINFO, &cache_info(60, 120)),
+            ],
+        );
+        let buffer = message(RTM_NEWADDR, 1, &body);
+
+        // 任何截断的数据报都返回错误而不是 panic
+        for end in 1..buffer.len() {
+            assert!(parse_messages(&buffer[..end]).is_err(), "prefix {}", end);
+        }
+        // 消息长度小于消息头
+        let mut short = buffer.clone();
+        short[..4].copy_from_slice(&8u32.to_ne_bytes());
+        assert!(parse_messages(&short).is_err());
+
+        // 截断的消息内容：不 panic，无法取得地址时返回 None
+        for end in 0..body.len() {
+            let parsed = parse_address(&body[..end]);
+            assert!(end >= 16 || parsed.is_none(), "prefix {}", end);
+        }
+        // 声明长度超出剩余数据的属性被忽略
+        let mut overflow = address(libc::AF_INET, 24, 2, &[]);
+        overflow.extend_from_slice(&64u16.to_ne_bytes());
+        overflow.extend_from_slice(&IFA_ADDRESS.to_ne_bytes());
+        overflow.extend_from_slice(&[192, 0, 2, 1]);
+        assert!(parse_address(&overflow).is_none());
+        // 地址长度与协议族不符
+        let mismatch = address(
+            libc::AF_INET6,
+            64,
+            2,
+            &[attribute(IFA_ADDRESS, &[192, 0, 2, 1])],
+        );
+        assert!(parse_address(&mismatch).is_none());
+    }
+}
@@ -0,0 +1,97 @@
+/// 读取系统路由表（Linux: /proc/net/route、/proc/net/ipv6_route）
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/// RTF_UP
+static RTF_UP: u32 = 0x0001;
+/// RTF_REJECT
+static RTF_REJECT: u32 = 0x0200;
+
+/// 默认路由
+#[derive(Debug, Clone)]
+pub struct DefaultRoute {
+    /// 出口网卡名称
+    pub interface: String,
+    /// 网关地址
+    pub gateway: Option<IpAddr>,
+    pub metric: u32,
+}
+
+/// 获取IPv4默认路由，按 metric 升序排列
+pub fn default_routes_v4() -> Vec<DefaultRoute> {
+    let content = match fs::read_to_string("/proc/net/route") {
+        Ok(content) => content,
+        Err(_) => return Vec::new(),
+    };
+
+    // Iface Destination Gateway Flags RefCnt Use Metric Mask MTU Window IRTT
+    let mut routes: Vec<DefaultRoute> = content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 {
+                return None;
+            }
+            let destination = u32::from_str_radix(fields[1], 16).ok()?;
+            let mask = u32::from_str_radix(fields[7], 16).ok()?;
+            let flags = u32::from_str_radix(fields[3], 16).ok()?;
+            if destination != 0 || mask != 0 || flags & RTF_UP == 0 || flags & RTF_REJECT != 0 {
+                return None;
+            }
+            // 地址为按主机字节序读出的网络字节序整数
+            let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+            Some(DefaultRoute {
+                interface: fields[0].to_string(),
+                gateway: match gateway {
+                    0 => None,
+                    gateway => Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(gateway)))),
+                },
+                metric: fields[6].parse().unwrap_or(0),
+            })
+        })
+        .collect();
+
+    routes.sort_by_key(|route| route.metric);
+    routes
+}
+
+/// 获取IPv6默认路由，按 metric 升序排列
+pub fn default_routes_v6() -> Vec<DefaultRoute> {
+    let content = match fs::read_to_string("/proc/net/ipv6_route") {
+        Ok(content) => content,
+        Err(_) => return Vec::new(),
+    };
+
+    // Destination PrefixLen Source SourcePrefixLen NextHop Metric RefCnt Use Flags Iface（均为十六进制）
+    let mut routes: Vec<DefaultRoute> = content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 {
+                return None;
+            }
+            let destination = u128::from_str_radix(fields[0], 16).ok()?;
+            let prefix_len = u8::from_str_radix(fields[1], 16).ok()?;
+            let flags = u32::from_str_radix(fields[8], 16).ok()?;
+            if destination != 0 || prefix_len != 0 || flags & RTF_UP == 0 || flags & RTF_REJECT != 0
+            {
+                return None;
+            }
+            let gateway = u128::from_str_radix(fields[4], 16).ok()?;
+            Some(DefaultRoute {
+                interface: fields[9].to_string(),
+                gateway: match gateway {
+                    0 => None,
+                    gateway => Some(IpAddr::V6(Ipv6Addr::from(gateway))),
+                },
+                metric: u32::from_str_radix(fields[5], 16).unwrap_or(0),
+            })
+        })
+        .collect();
+
+    routes.sort_by_key(|route| route.metric);
+    routes
+}